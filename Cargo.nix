@@ -1002,6 +1002,25 @@ rec {
         };
         resolvedDefaultFeatures = [ "default" "std" ];
       };
+      "crossbeam-channel" = rec {
+        crateName = "crossbeam-channel";
+        version = "0.5.15";
+        edition = "2021";
+        sha256 = "1cicd9ins0fkpfgvz9vhz3m9rpkh6n8d3437c3wnfsdkd3wgif42";
+        libName = "crossbeam_channel";
+        dependencies = [
+          {
+            name = "crossbeam-utils";
+            packageId = "crossbeam-utils";
+            usesDefaultFeatures = false;
+          }
+        ];
+        features = {
+          "default" = [ "std" ];
+          "std" = [ "crossbeam-utils/std" ];
+        };
+        resolvedDefaultFeatures = [ "default" "std" ];
+      };
       "crossbeam-deque" = rec {
         crateName = "crossbeam-deque";
         version = "0.8.5";
@@ -1626,26 +1645,6 @@ rec {
         };
         resolvedDefaultFeatures = [ "color_quant" "default" "raii_no_panic" "std" ];
       };
-      "globmatch" = rec {
-        crateName = "globmatch";
-        version = "0.3.0";
-        edition = "2021";
-        sha256 = "1n101a48xnyqnmqlyil3p2c36lqipfgrbn03jgkvrqvhdq61bm65";
-        authors = [
-          "Martin Lampacher <lmapii@gmail.com>"
-        ];
-        dependencies = [
-          {
-            name = "globset";
-            packageId = "globset";
-          }
-          {
-            name = "walkdir";
-            packageId = "walkdir";
-          }
-        ];
-
-      };
       "globset" = rec {
         crateName = "globset";
         version = "0.4.15";
@@ -2453,6 +2452,54 @@ rec {
         ];
 
       };
+      "ignore" = rec {
+        crateName = "ignore";
+        version = "0.4.23";
+        edition = "2021";
+        sha256 = "0jysggjfmlxbg60vhhiz4pb8jfb7cnq5swdsvxknbs7x18wgv2bd";
+        authors = [
+          "Andrew Gallant <jamslam@gmail.com>"
+        ];
+        dependencies = [
+          {
+            name = "crossbeam-deque";
+            packageId = "crossbeam-deque";
+          }
+          {
+            name = "globset";
+            packageId = "globset";
+          }
+          {
+            name = "log";
+            packageId = "log";
+          }
+          {
+            name = "memchr";
+            packageId = "memchr";
+          }
+          {
+            name = "regex-automata";
+            packageId = "regex-automata 0.4.8";
+            usesDefaultFeatures = false;
+            features = [ "std" "perf" "syntax" "meta" "nfa" "hybrid" "dfa-onepass" ];
+          }
+          {
+            name = "same-file";
+            packageId = "same-file";
+          }
+          {
+            name = "walkdir";
+            packageId = "walkdir";
+          }
+          {
+            name = "winapi-util";
+            packageId = "winapi-util";
+            target = { target, features }: (target."windows" or false);
+          }
+        ];
+        features = {
+        };
+      };
       "image" = rec {
         crateName = "image";
         version = "0.24.9";
@@ -2837,6 +2884,69 @@ rec {
         };
         resolvedDefaultFeatures = [ "std" ];
       };
+      "lsp-server" = rec {
+        crateName = "lsp-server";
+        version = "0.7.6";
+        edition = "2021";
+        sha256 = "15bhdhkinhhw5fifrpmiiqdd4hwblac40jv0n7hxidbdiyvnb3r4";
+        dependencies = [
+          {
+            name = "crossbeam-channel";
+            packageId = "crossbeam-channel";
+          }
+          {
+            name = "log";
+            packageId = "log";
+          }
+          {
+            name = "serde";
+            packageId = "serde";
+            features = [ "derive" ];
+          }
+          {
+            name = "serde_json";
+            packageId = "serde_json";
+          }
+        ];
+
+      };
+      "lsp-types" = rec {
+        crateName = "lsp-types";
+        version = "0.95.1";
+        edition = "2018";
+        sha256 = "0ra36fd4yr7lf5igfrdvwjx9g87z3a99mrjgzk9nq04viqxd6d4f";
+        authors = [
+          "Markus Westerlind <marwes91@gmail.com>"
+          "Bruno Medeiros <bruno.do.medeiros@gmail.com>"
+        ];
+        dependencies = [
+          {
+            name = "bitflags";
+            packageId = "bitflags 1.3.2";
+          }
+          {
+            name = "serde";
+            packageId = "serde";
+            features = [ "derive" ];
+          }
+          {
+            name = "serde_json";
+            packageId = "serde_json";
+          }
+          {
+            name = "serde_repr";
+            packageId = "serde_repr";
+          }
+          {
+            name = "url";
+            packageId = "url";
+            features = [ "serde" ];
+          }
+        ];
+        features = {
+        };
+        resolvedDefaultFeatures = [ "default" ];
+      };
       "matchers" = rec {
         crateName = "matchers";
         version = "0.1.0";
@@ -4229,6 +4339,31 @@ rec {
         };
         resolvedDefaultFeatures = [ "default" "std" ];
       };
+      "serde_repr" = rec {
+        crateName = "serde_repr";
+        version = "0.1.19";
+        edition = "2021";
+        sha256 = "1sb4cplc33z86pzlx38234xr141wr3cmviqgssiadisgl8dlar3c";
+        procMacro = true;
+        authors = [
+          "David Tolnay <dtolnay@gmail.com>"
+        ];
+        dependencies = [
+          {
+            name = "proc-macro2";
+            packageId = "proc-macro2";
+          }
+          {
+            name = "quote";
+            packageId = "quote";
+          }
+          {
+            name = "syn";
+            packageId = "syn";
+          }
+        ];
+
+      };
       "serde_spanned" = rec {
         crateName = "serde_spanned";
         version = "0.6.8";
@@ -5959,18 +6094,38 @@ rec {
             features = [ "derive" ];
           }
           {
-            name = "globmatch";
-            packageId = "globmatch";
+            name = "ignore";
+            packageId = "ignore";
+          }
+          {
+            name = "lsp-server";
+            packageId = "lsp-server";
+          }
+          {
+            name = "lsp-types";
+            packageId = "lsp-types";
+          }
+          {
+            name = "rayon";
+            packageId = "rayon";
           }
           {
             name = "serde";
             packageId = "serde";
             features = [ "derive" ];
           }
+          {
+            name = "serde_json";
+            packageId = "serde_json";
+          }
           {
             name = "similar";
             packageId = "similar";
           }
+          {
+            name = "siphasher";
+            packageId = "siphasher 1.0.1";
+          }
           {
             name = "thiserror";
             packageId = "thiserror";
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use serde::Serialize;
+use siphasher::sip128::Hasher128;
+use siphasher::sip128::SipHasher13;
+use tracing::debug;
+use typstfmt::Config;
+
+/// The name of the file the cache is stored in, within the cache directory.
+const CACHE_FILE_NAME: &str = "cache.json";
+
+/// Remembers the files that are already formatted, so that they can be skipped without parsing.
+///
+/// Each file is stored with a key made from its content, the build of typstfmt and the config it
+/// was formatted with, so changing any of them means the file gets formatted again.
+pub struct Cache {
+    path: PathBuf,
+    contents: CacheContents,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct CacheContents {
+    /// The build of typstfmt that wrote the cache.
+    version: String,
+    /// The key of each file from when it was last known to be formatted.
+    files: HashMap<PathBuf, String>,
+}
+
+impl Cache {
+    /// Load the cache from the directory, starting afresh if there isn't a usable one.
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(CACHE_FILE_NAME);
+        let contents = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<CacheContents>(&content).ok())
+            .filter(|contents| contents.version == build())
+            .unwrap_or_else(|| {
+                debug!(?path, "Starting a new cache");
+                CacheContents {
+                    version: build().to_owned(),
+                    files: HashMap::new(),
+                }
+            });
+        Self { path, contents }
+    }
+
+    /// The key for a file with the given content formatted with the given config.
+    pub fn key(config: &Config, content: &str) -> String {
+        let mut hasher = SipHasher13::new();
+        build().hash(&mut hasher);
+        config.hash(&mut hasher);
+        hasher.write(content.as_bytes());
+        format!("{:032x}", hasher.finish128().as_u128())
+    }
+
+    /// Whether the file is known to be formatted, from the key of its current content.
+    pub fn is_formatted(&self, file: &Path, key: &str) -> bool {
+        self.contents
+            .files
+            .get(&canonical(file))
+            .is_some_and(|cached| cached == key)
+    }
+
+    /// Remember that the file is formatted, with the key of its formatted content.
+    pub fn insert(&mut self, file: &Path, key: String) {
+        self.contents.files.insert(canonical(file), key);
+    }
+
+    /// Write the cache out, replacing the old one all at once so that concurrent runs don't leave
+    /// a broken file behind.
+    pub fn save(&self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temp = self
+            .path
+            .with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temp, serde_json::to_string(&self.contents)?)?;
+        fs::rename(&temp, &self.path)
+    }
+}
+
+/// The directory to keep the cache in by default, within the user's cache directory.
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let user_cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+    Some(user_cache.join("typstfmt"))
+}
+
+/// What identifies the build of typstfmt that is running, its version along with a hash of its
+/// binary, as a rebuild can change the formatting without changing the version.
+fn build() -> &'static str {
+    static BUILD: OnceLock<String> = OnceLock::new();
+    BUILD.get_or_init(|| {
+        let binary = std::env::current_exe().and_then(fs::read);
+        let id = match binary {
+            Ok(binary) => {
+                let mut hasher = SipHasher13::new();
+                hasher.write(&binary);
+                format!("{:032x}", hasher.finish128().as_u128())
+            }
+            Err(error) => {
+                // nothing cached by this run can be trusted by another
+                debug!(%error, "Failed to read the running binary");
+                format!("unknown-{}", std::process::id())
+            }
+        };
+        format!("{}+{id}", env!("CARGO_PKG_VERSION"))
+    })
+}
+
+/// Files are stored by their absolute path, so that the cache works from any directory.
+fn canonical(file: &Path) -> PathBuf {
+    file.canonicalize().unwrap_or_else(|_| file.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembers_formatted_files() {
+        let dir = std::env::temp_dir().join(format!("typstfmt-cache-{}", std::process::id()));
+        let file = dir.join("doc.typ");
+        let config = Config::default();
+        let key = Cache::key(&config, "#let x = 1\n");
+
+        let mut cache = Cache::load(&dir);
+        assert!(!cache.is_formatted(&file, &key));
+        cache.insert(&file, key.clone());
+        cache.save().unwrap();
+
+        let cache = Cache::load(&dir);
+        assert!(cache.is_formatted(&file, &key));
+        assert!(!cache.is_formatted(&file, &Cache::key(&config, "#let x = 2\n")));
+        let other_config = Config {
+            indent: 4,
+            ..Config::default()
+        };
+        assert!(!cache.is_formatted(&file, &Cache::key(&other_config, "#let x = 1\n")));
+
+        // a cache from another build of the same version is dropped
+        let mut other_build = Cache::load(&dir);
+        other_build.contents.version = env!("CARGO_PKG_VERSION").to_owned();
+        other_build.save().unwrap();
+        assert!(!Cache::load(&dir).is_formatted(&file, &key));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
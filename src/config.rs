@@ -1,14 +1,163 @@
 use serde::{Deserialize, Serialize};
 
 /// Configuration options for the formatting.
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Hash, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     /// The number of spaces to indent by.
     pub indent: usize,
+    /// Whether to indent with spaces or tabs.
+    pub indent_style: IndentStyle,
+    /// The width of a tab, for working out how wide lines are when indenting with tabs.
+    pub tab_width: usize,
+    /// The maximum width of a line, groups that would exceed it are broken over multiple lines.
+    pub max_width: usize,
+    /// The line endings to write.
+    pub newline_style: NewlineStyle,
+    /// When to put a comma after the last item of arguments, parameters, arrays and dictionaries.
+    pub trailing_comma: TrailingComma,
+    /// Whether to lay out the cells of `table` and `grid` calls with a literal number of columns
+    /// as rows, with the cells of each column aligned, when the rows fit within `max_width`.
+    pub align_table_columns: bool,
+    /// Whether to align the descriptions of consecutive term items, filling them with a hanging
+    /// indent.
+    pub align_term_descriptions: bool,
+    /// How the text of paragraphs is broken over lines.
+    pub markup_wrap: MarkupWrap,
+    /// The number of blank lines to put before and after headings, or none to keep those written.
+    ///
+    /// This is kept even when it is more than `max_blank_lines`.
+    pub blank_lines_around_headings: Option<usize>,
+    /// How the markers of enum items are written.
+    pub enum_marker: EnumMarker,
+    /// The most blank lines to keep in a row, in both markup and code.
+    ///
+    /// The blank line of a paragraph break is always kept, so zero acts like one.
+    pub max_blank_lines: usize,
+    /// The number of blank lines to start a document with.
+    pub blank_lines_at_start: usize,
+    /// The number of blank lines to end a document with.
+    pub blank_lines_at_end: usize,
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Config { indent: 2 }
+        Config {
+            indent: 2,
+            indent_style: IndentStyle::Spaces,
+            tab_width: 4,
+            max_width: 80,
+            newline_style: NewlineStyle::Auto,
+            trailing_comma: TrailingComma::Multiline,
+            align_table_columns: false,
+            align_term_descriptions: false,
+            markup_wrap: MarkupWrap::Preserve,
+            blank_lines_around_headings: None,
+            enum_marker: EnumMarker::Preserve,
+            max_blank_lines: 1,
+            blank_lines_at_start: 0,
+            blank_lines_at_end: 0,
+        }
     }
 }
+
+impl Config {
+    /// The width of one level of indentation.
+    pub fn indent_width(&self) -> usize {
+        match self.indent_style {
+            IndentStyle::Spaces => self.indent,
+            IndentStyle::Tabs => self.tab_width,
+        }
+    }
+}
+
+/// What lines are indented with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndentStyle {
+    /// `indent` spaces for each level.
+    #[default]
+    Spaces,
+    /// A tab for each level.
+    Tabs,
+}
+
+/// What lines are ended with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NewlineStyle {
+    /// Whichever of `\n` and `\r\n` ends most lines of the input.
+    #[default]
+    Auto,
+    /// `\n`.
+    Lf,
+    /// `\r\n`.
+    Crlf,
+    /// `\r\n` on Windows and `\n` everywhere else.
+    Native,
+}
+
+impl NewlineStyle {
+    /// The concrete style to write the given input with, either `Lf` or `Crlf`.
+    pub(crate) fn resolve(self, input: &str) -> Self {
+        match self {
+            NewlineStyle::Auto => {
+                let crlf = input.matches("\r\n").count();
+                let lf = input.matches('\n').count() - crlf;
+                if crlf > lf {
+                    NewlineStyle::Crlf
+                } else {
+                    NewlineStyle::Lf
+                }
+            }
+            NewlineStyle::Native if cfg!(windows) => NewlineStyle::Crlf,
+            NewlineStyle::Native => NewlineStyle::Lf,
+            style => style,
+        }
+    }
+}
+
+/// When the last item of a comma separated group is followed by a comma.
+///
+/// An array of a single item always keeps its comma, as without it the parentheses are just
+/// grouping the item.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingComma {
+    /// On a single line as well as when broken over lines.
+    Always,
+    /// Never.
+    Never,
+    /// Only when broken over lines.
+    #[default]
+    Multiline,
+}
+
+/// How the text of paragraphs is broken over lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MarkupWrap {
+    /// Keep the lines as they were written.
+    #[default]
+    Preserve,
+    /// Fill each line with as many words as fit within `max_width`.
+    Fill,
+    /// Put each sentence on a line of its own.
+    Sentence,
+}
+
+/// How the markers of enum items are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnumMarker {
+    /// Keep the markers as they were written.
+    #[default]
+    Preserve,
+    /// `+` for every item that would be numbered one more than the item before it anyway, and the
+    /// number for any other.
+    Plus,
+    /// Numbers counting up from one.
+    Sequential,
+    /// Numbers counting up from the number of the first item.
+    KeepFirst,
+}
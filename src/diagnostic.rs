@@ -0,0 +1,124 @@
+use std::ops::Range;
+
+use serde::Serialize;
+use similar::{DiffTag, TextDiff};
+use typst::syntax::{SyntaxKind, SyntaxNode};
+
+/// A message about part of some typst code, such as a parser error, along with where it is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// What is wrong with the code.
+    pub message: String,
+    /// The byte range of the code the message is about.
+    pub range: Range<usize>,
+    /// The line the range starts on, counting from 1.
+    pub line: usize,
+    /// The column the range starts at, in characters and counting from 1.
+    pub column: usize,
+    /// The whole line the range starts on, without its newline.
+    pub source_line: String,
+}
+
+/// The first region where two versions of some text differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    /// The line the region starts on, counting from 1.
+    pub line: usize,
+    /// The lines of the region in the first version.
+    pub before: String,
+    /// The lines of the region in the second version.
+    pub after: String,
+}
+
+/// The diagnostics for each error node in the tree, which was parsed from `text`.
+pub(crate) fn diagnostics(root: &SyntaxNode, text: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    collect(root, 0, text, &mut diagnostics);
+    diagnostics
+}
+
+fn collect(node: &SyntaxNode, offset: usize, text: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if node.kind() == SyntaxKind::Error {
+        let message = node
+            .errors()
+            .into_iter()
+            .next()
+            .map_or_else(|| "syntax error".to_owned(), |error| error.message.into());
+        diagnostics.push(diagnostic(text, offset..offset + node.len(), message));
+        return;
+    }
+    let mut offset = offset;
+    for child in node.children() {
+        collect(child, offset, text, diagnostics);
+        offset += child.len();
+    }
+}
+
+/// Make a diagnostic for the byte range of the text.
+pub(crate) fn diagnostic(text: &str, range: Range<usize>, message: String) -> Diagnostic {
+    let before = &text[..range.start];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[line_start..]
+        .find('\n')
+        .map_or(text.len(), |i| line_start + i);
+    Diagnostic {
+        message,
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+        source_line: text[line_start..line_end].trim_end_matches('\r').to_owned(),
+        range,
+    }
+}
+
+/// Find the first lines that differ between the two texts, if any do.
+pub(crate) fn first_difference(before: &str, after: &str) -> Option<Difference> {
+    let diff = TextDiff::from_lines(before, after);
+    // neighbouring deletes and inserts form a single region
+    let mut ops = diff
+        .ops()
+        .iter()
+        .skip_while(|op| op.tag() == DiffTag::Equal);
+    let first = ops.next()?;
+    let last = ops
+        .take_while(|op| op.tag() != DiffTag::Equal)
+        .last()
+        .unwrap_or(first);
+    let old_range = first.old_range().start..last.old_range().end;
+    let new_range = first.new_range().start..last.new_range().end;
+    Some(Difference {
+        line: old_range.start + 1,
+        before: diff.old_slices()[old_range].concat(),
+        after: diff.new_slices()[new_range].concat(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use typst::syntax::parse;
+
+    use super::*;
+
+    #[test]
+    fn diagnostic_position() {
+        let text = "#let x = 1\n#let α = (1,2";
+        let diagnostics = diagnostics(&parse(text), text);
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!((diagnostic.line, diagnostic.column), (2, 10));
+        assert_eq!(diagnostic.source_line, "#let α = (1,2");
+    }
+
+    #[test]
+    fn difference_region() {
+        let difference = first_difference("a\nb\nc\nd\n", "a\nB\nC\nd\n").unwrap();
+        assert_eq!(
+            difference,
+            Difference {
+                line: 2,
+                before: "b\nc\n".to_owned(),
+                after: "B\nC\n".to_owned(),
+            }
+        );
+        assert_eq!(first_difference("a\n", "a\n"), None);
+    }
+}
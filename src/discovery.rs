@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+use tracing::debug;
+use typstfmt::Config;
+
+use crate::walk::WalkConfig;
+use crate::Error;
+
+/// The names of config files, in order of preference when a directory has more than one.
+const CONFIG_FILE_NAMES: [&str; 2] = ["typstfmt.toml", ".typstfmt.toml"];
+
+/// A config along with the file it was loaded from.
+#[derive(Clone, Default)]
+pub struct ResolvedConfig {
+    pub config: Config,
+    /// The options for walking directories from the same file.
+    pub walk: WalkConfig,
+    /// The file the config was loaded from, or none if it is the default.
+    pub path: Option<PathBuf>,
+}
+
+/// Finds the config to format each file with, from the nearest config file in its directory or
+/// the directories above it.
+pub struct ConfigResolver {
+    /// The config to use for every file instead of searching for one.
+    fixed: Option<ResolvedConfig>,
+    /// The config that applies in each directory searched so far.
+    cache: HashMap<PathBuf, ResolvedConfig>,
+}
+
+impl ConfigResolver {
+    /// Create a resolver, which always uses the given config file if there is one.
+    ///
+    /// A config file that was asked for has to exist.
+    pub fn new(config_path: Option<&Path>) -> Result<Self, Error> {
+        let fixed = config_path
+            .map(|path| {
+                if !path.is_file() {
+                    return Err(Error::MissingConfig(path.to_owned()));
+                }
+                load_config(path)
+            })
+            .transpose()?;
+        Ok(Self {
+            fixed,
+            cache: HashMap::new(),
+        })
+    }
+
+    /// The config for formatting the file at the given path.
+    pub fn resolve(&mut self, file: &Path) -> Result<ResolvedConfig, Error> {
+        let dir = file.parent().unwrap_or(Path::new(""));
+        self.resolve_dir(dir)
+    }
+
+    /// The config for formatting files in the given directory.
+    pub fn resolve_dir(&mut self, dir: &Path) -> Result<ResolvedConfig, Error> {
+        if let Some(fixed) = &self.fixed {
+            return Ok(fixed.clone());
+        }
+
+        // search from an absolute path so that the search can go above the working directory
+        let dir = dir
+            .canonicalize()
+            .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(dir)))?;
+
+        let mut searched = Vec::new();
+        let mut found = None;
+        for ancestor in dir.ancestors() {
+            if let Some(resolved) = self.cache.get(ancestor) {
+                found = Some(resolved.clone());
+                break;
+            }
+            searched.push(ancestor.to_owned());
+            let config_file = CONFIG_FILE_NAMES
+                .iter()
+                .map(|name| ancestor.join(name))
+                .find(|path| path.is_file());
+            if let Some(path) = config_file {
+                found = Some(load_config(&path)?);
+                break;
+            }
+        }
+
+        let resolved = found.unwrap_or_default();
+        debug!(?dir, path = ?resolved.path, "Resolved config");
+        for searched_dir in searched {
+            self.cache.insert(searched_dir, resolved.clone());
+        }
+        Ok(resolved)
+    }
+}
+
+/// Load the config from the given file.
+pub fn load_config(config_path: &Path) -> Result<ResolvedConfig, Error> {
+    debug!(?config_path, "Loading config from file");
+    let mut config_file = File::open(config_path)?;
+    let mut config_file_content = String::new();
+    config_file.read_to_string(&mut config_file_content)?;
+    Ok(ResolvedConfig {
+        config: toml::from_str(&config_file_content)?,
+        walk: toml::from_str(&config_file_content)?,
+        path: Some(config_path.to_owned()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn nearest_config_applies() {
+        let root = std::env::temp_dir().join(format!("typstfmt-discovery-{}", std::process::id()));
+        let project = root.join("project");
+        let chapters = project.join("chapters");
+        fs::create_dir_all(&chapters).unwrap();
+        fs::write(
+            root.join("typstfmt.toml"),
+            "indent = 4\nexclude = [\"drafts\"]",
+        )
+        .unwrap();
+        fs::write(project.join(".typstfmt.toml"), "indent = 8").unwrap();
+
+        let mut resolver = ConfigResolver::new(None).unwrap();
+        let top = resolver.resolve(&root.join("doc.typ")).unwrap();
+        let nested = resolver.resolve(&chapters.join("doc.typ")).unwrap();
+        assert_eq!(top.config.indent, 4);
+        assert_eq!(top.walk.exclude, ["drafts"]);
+        assert_eq!(nested.config.indent, 8);
+        assert_eq!(
+            nested.path,
+            Some(project.canonicalize().unwrap().join(".typstfmt.toml"))
+        );
+
+        let mut fixed = ConfigResolver::new(Some(&root.join("typstfmt.toml"))).unwrap();
+        assert_eq!(
+            fixed
+                .resolve(&chapters.join("doc.typ"))
+                .unwrap()
+                .config
+                .indent,
+            4
+        );
+        assert!(matches!(
+            ConfigResolver::new(Some(&root.join("missing.toml"))),
+            Err(Error::MissingConfig(_))
+        ));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+use std::ops::Range;
+
+use similar::{DiffTag, TextDiff};
+
+use crate::{format, Config, FormatError};
+
+/// A replacement of part of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// The byte range of the input to replace.
+    pub range: Range<usize>,
+    /// The text to put in place of the range.
+    pub text: String,
+}
+
+/// Format some typst code, returning the edits to apply to the input rather than the whole output.
+///
+/// The edits are minimal, sorted by position and never overlap.
+/// Applying them from last to first turns the input into the output of [`format()`].
+pub fn format_edits(input: &str, config: &Config) -> Result<Vec<TextEdit>, FormatError> {
+    let formatted = format(input, config.clone())?;
+    Ok(diff(input, &formatted))
+}
+
+/// Compute the edits to turn `old` into `new`.
+///
+/// Changed lines are found first, and then refined down to the characters that differ.
+fn diff(old: &str, new: &str) -> Vec<TextEdit> {
+    let line_diff = TextDiff::from_lines(old, new);
+    let old_offsets = slice_offsets(line_diff.old_slices());
+    let new_offsets = slice_offsets(line_diff.new_slices());
+
+    let mut edits = Vec::new();
+    // a line of context lets changes that are split by a matching blank line be refined together
+    for group in line_diff.grouped_ops(1) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old_range = old_offsets[first.old_range().start]..old_offsets[last.old_range().end];
+        let new_range = new_offsets[first.new_range().start]..new_offsets[last.new_range().end];
+        let old_text = &old[old_range.clone()];
+        let new_text = &new[new_range];
+        for (old_change, new_change) in changes(TextDiff::from_chars(old_text, new_text)) {
+            edits.push(TextEdit {
+                range: old_range.start + old_change.start..old_range.start + old_change.end,
+                text: new_text[new_change].to_owned(),
+            });
+        }
+    }
+    edits
+}
+
+/// The byte ranges of the changed parts of the old and new texts.
+///
+/// Neighbouring changes, such as a deletion followed by an insertion, are merged.
+fn changes(text_diff: TextDiff<str>) -> Vec<(Range<usize>, Range<usize>)> {
+    let old_offsets = slice_offsets(text_diff.old_slices());
+    let new_offsets = slice_offsets(text_diff.new_slices());
+
+    let mut changes: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+    for op in text_diff.ops() {
+        if op.tag() == DiffTag::Equal {
+            continue;
+        }
+        let old_range = old_offsets[op.old_range().start]..old_offsets[op.old_range().end];
+        let new_range = new_offsets[op.new_range().start]..new_offsets[op.new_range().end];
+        match changes.last_mut() {
+            Some((last_old, last_new))
+                if last_old.end == old_range.start && last_new.end == new_range.start =>
+            {
+                last_old.end = old_range.end;
+                last_new.end = new_range.end;
+            }
+            _ => changes.push((old_range, new_range)),
+        }
+    }
+    changes
+}
+
+/// The byte offset of the start of each slice, and of the end of the text.
+fn slice_offsets(slices: &[&str]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(slices.len() + 1);
+    let mut offset = 0;
+    offsets.push(offset);
+    for slice in slices {
+        offset += slice.len();
+        offsets.push(offset);
+    }
+    offsets
+}
@@ -0,0 +1,334 @@
+use std::ops::Range;
+
+use typst::syntax::{LinkedNode, SyntaxKind, SyntaxNode};
+
+use crate::config::EnumMarker;
+use crate::diagnostic::diagnostic;
+use crate::FormatError;
+
+/// Check that the formatted code means the same as the input.
+///
+/// The trees are compared node by node, ignoring the things formatting is allowed to change: the
+/// amount of whitespace, whitespace in code, space at the edges of markup and commas.
+/// The markers of enum items are only compared when they are kept as written.
+pub(crate) fn check_equivalent(
+    input: &SyntaxNode,
+    input_text: &str,
+    output: &SyntaxNode,
+    output_text: &str,
+    enum_marker: EnumMarker,
+) -> Result<(), FormatError> {
+    let renumbered = enum_marker != EnumMarker::Preserve;
+    match compare(
+        &LinkedNode::new(input),
+        &LinkedNode::new(output),
+        renumbered,
+    ) {
+        None => Ok(()),
+        Some((before, after)) => Err(FormatError::ChangedMeaning {
+            before: Box::new(diagnostic(
+                input_text,
+                before.range,
+                format!("the input has {}", before.description),
+            )),
+            after: Box::new(diagnostic(
+                output_text,
+                after.range,
+                format!("but the output has {}", after.description),
+            )),
+        }),
+    }
+}
+
+/// A place in one of the trees being compared.
+struct Place {
+    range: Range<usize>,
+    description: String,
+}
+
+impl Place {
+    /// The end of a node, which has no more children.
+    fn end(node: &LinkedNode) -> Self {
+        let end = node.range().end;
+        Self {
+            range: end..end,
+            description: format!("the end of the {}", node.kind().name()),
+        }
+    }
+}
+
+/// A part of a node that has to be kept by formatting.
+enum Item<'a> {
+    Node(LinkedNode<'a>),
+    /// A run of text and spaces in markup, with each stretch of whitespace made a single space.
+    ///
+    /// The parser only splits text at some spaces, so a run can be split differently once
+    /// reformatted.
+    Words {
+        range: Range<usize>,
+        text: String,
+    },
+}
+
+impl Item<'_> {
+    fn place(&self) -> Place {
+        match self {
+            Item::Node(node) => {
+                let description = if node.children().len() == 0 && node.kind() != SyntaxKind::Space
+                {
+                    format!("{} `{}`", node.kind().name(), node.text())
+                } else {
+                    node.kind().name().to_owned()
+                };
+                Place {
+                    range: node.range(),
+                    description,
+                }
+            }
+            Item::Words { range, text } => Place {
+                range: range.clone(),
+                description: format!("text `{text}`"),
+            },
+        }
+    }
+}
+
+/// Find the first places where the two nodes differ.
+fn compare(before: &LinkedNode, after: &LinkedNode, renumbered: bool) -> Option<(Place, Place)> {
+    if before.kind() != after.kind() || !same_text(before.get(), after.get(), renumbered) {
+        let places = (
+            Item::Node(before.clone()).place(),
+            Item::Node(after.clone()).place(),
+        );
+        return Some(places);
+    }
+
+    let before_items = significant_items(before);
+    let after_items = significant_items(after);
+    for i in 0..before_items.len().max(after_items.len()) {
+        let places = match (before_items.get(i), after_items.get(i)) {
+            (Some(Item::Node(before_child)), Some(Item::Node(after_child))) => {
+                compare(before_child, after_child, renumbered)
+            }
+            (
+                Some(Item::Words {
+                    text: before_text, ..
+                }),
+                Some(Item::Words {
+                    text: after_text, ..
+                }),
+            ) if before_text == after_text => None,
+            (before_item, after_item) => Some((
+                before_item.map_or_else(|| Place::end(before), Item::place),
+                after_item.map_or_else(|| Place::end(after), Item::place),
+            )),
+        };
+        if places.is_some() {
+            return places;
+        }
+    }
+    None
+}
+
+/// Whether the text of two leaves of the same kind means the same thing.
+fn same_text(before: &SyntaxNode, after: &SyntaxNode, renumbered: bool) -> bool {
+    match before.kind() {
+        // only whether there is a space matters, not how much
+        SyntaxKind::Space | SyntaxKind::Parbreak => true,
+        SyntaxKind::EnumMarker if renumbered => true,
+        SyntaxKind::LineComment => before
+            .text()
+            .split_whitespace()
+            .eq(after.text().split_whitespace()),
+        // block comments get reindented and a `*` put at the start of their lines
+        SyntaxKind::BlockComment => comment_words(before.text()).eq(comment_words(after.text())),
+        // the line endings of raw text and strings get converted with the rest of the file
+        _ => {
+            before.text() == after.text()
+                || before.text().replace("\r\n", "\n") == after.text().replace("\r\n", "\n")
+        }
+    }
+}
+
+/// The words of a block comment, without its delimiters or a `*` starting each line.
+fn comment_words(comment: &str) -> impl Iterator<Item = &str> {
+    let inner = comment.strip_prefix("/*").unwrap_or(comment);
+    let inner = inner.strip_suffix("*/").unwrap_or(inner);
+    inner.lines().enumerate().flat_map(|(i, line)| {
+        let line = line.trim_start();
+        let line = match line.strip_prefix('*') {
+            Some(rest) if i > 0 => rest,
+            _ => line,
+        };
+        line.split_whitespace()
+    })
+}
+
+/// Whether the node is the arguments of a function call in math or a row of a matrix, where a
+/// trailing comma adds an empty item.
+fn is_math_group(node: &LinkedNode) -> bool {
+    fn is_math_callee(node: &SyntaxNode) -> bool {
+        match node.kind() {
+            SyntaxKind::MathIdent => true,
+            SyntaxKind::FieldAccess => node.children().next().is_some_and(is_math_callee),
+            _ => false,
+        }
+    }
+    match node.kind() {
+        SyntaxKind::Args => node
+            .parent()
+            .and_then(|call| call.children().next())
+            .is_some_and(|callee| is_math_callee(callee.get())),
+        SyntaxKind::Array => node
+            .children()
+            .next()
+            .is_some_and(|first| first.kind() != SyntaxKind::LeftParen),
+        _ => false,
+    }
+}
+
+/// The parts of the node that formatting has to preserve.
+fn significant_items<'a>(node: &LinkedNode<'a>) -> Vec<Item<'a>> {
+    // spaces separate words in markup and make equations display ones
+    let keep_spaces = matches!(node.kind(), SyntaxKind::Markup | SyntaxKind::Equation);
+    let keep_commas = is_math_group(node);
+    let mut children: Vec<_> = node
+        .children()
+        .filter(|child| match child.kind() {
+            SyntaxKind::Comma => keep_commas,
+            SyntaxKind::Space => keep_spaces,
+            _ => true,
+        })
+        .collect();
+
+    if node.kind() != SyntaxKind::Markup {
+        return children.into_iter().map(Item::Node).collect();
+    }
+
+    let is_blank =
+        |child: &LinkedNode| matches!(child.kind(), SyntaxKind::Space | SyntaxKind::Parbreak);
+    // headings are blocks of their own, so it doesn't matter how many lines separate them
+    let is_heading =
+        |child: Option<&LinkedNode>| child.is_some_and(|c| c.kind() == SyntaxKind::Heading);
+    children.retain(|child| {
+        !(is_blank(child)
+            && (is_heading(child.prev_sibling().as_ref())
+                || is_heading(child.next_sibling().as_ref())))
+    });
+    while children.last().is_some_and(is_blank) {
+        children.pop();
+    }
+    let leading = children.iter().take_while(|child| is_blank(child)).count();
+
+    let mut items = Vec::new();
+    for child in children.into_iter().skip(leading) {
+        if !matches!(child.kind(), SyntaxKind::Text | SyntaxKind::Space) {
+            items.push(Item::Node(child));
+            continue;
+        }
+        let words = child
+            .text()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut text = String::new();
+        if child.text().starts_with(char::is_whitespace) {
+            text.push(' ');
+        }
+        text.push_str(&words);
+        if !words.is_empty() && child.text().ends_with(char::is_whitespace) {
+            text.push(' ');
+        }
+        match items.last_mut() {
+            Some(Item::Words {
+                range,
+                text: previous,
+            }) => {
+                range.end = child.range().end;
+                if previous.ends_with(' ') && text.starts_with(' ') {
+                    text.remove(0);
+                }
+                previous.push_str(&text);
+            }
+            _ => items.push(Item::Words {
+                range: child.range(),
+                text,
+            }),
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use typst::syntax::parse;
+
+    use super::*;
+
+    fn check(input: &str, output: &str) -> Result<(), FormatError> {
+        check_equivalent(
+            &parse(input),
+            input,
+            &parse(output),
+            output,
+            EnumMarker::Preserve,
+        )
+    }
+
+    #[test]
+    fn whitespace_is_not_significant() {
+        assert!(check(
+            "#f(a,b)\n\n\nsome  text\n",
+            "#f(\n  a,\n  b,\n)\n\nsome text"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn enum_markers_are_significant_unless_renumbered() {
+        let (input, output) = ("3. a\n+ b", "+ a\n+ b");
+        assert!(check(input, output).is_err());
+        let renumbered = check_equivalent(
+            &parse(input),
+            input,
+            &parse(output),
+            output,
+            EnumMarker::Sequential,
+        );
+        assert!(renumbered.is_ok());
+    }
+
+    #[test]
+    fn trailing_commas_are_significant_in_math() {
+        assert!(check("$vec(x, y)$", "$vec(x, y,)$").is_err());
+        assert!(check("$mat(1, 2; 3, 4)$", "$mat(1, 2; 3, 4,)$").is_err());
+        assert!(check("#f(x, y)", "#f(x, y,)").is_ok());
+    }
+
+    #[test]
+    fn lines_around_headings_are_not_significant() {
+        assert!(check("text\n= Heading\nmore", "text\n\n= Heading\n\nmore").is_ok());
+        assert!(check("text\nmore", "text\n\nmore").is_err());
+    }
+
+    #[test]
+    fn dropped_space_in_markup() {
+        let Err(FormatError::ChangedMeaning { before, after }) = check("some text", "sometext")
+        else {
+            panic!("expected the meaning to change");
+        };
+        assert_eq!(before.range, 0..9);
+        assert_eq!(after.range, 0..8);
+    }
+
+    #[test]
+    fn changed_string() {
+        let Err(FormatError::ChangedMeaning { before, after }) =
+            check("#let x = \"a b\"", "#let x = \"a  b\"")
+        else {
+            panic!("expected the meaning to change");
+        };
+        assert_eq!(before.range, 9..14);
+        assert_eq!(after.message, "but the output has string `\"a  b\"`");
+    }
+}
@@ -6,15 +6,29 @@
 //!
 //! [`format()`] is the main point of interest, with [`Config`] for adding some options on how things
 //! get formatted.
+//! [`format_range()`] formats just part of the input, such as for an editor's selection.
+//! [`format_edits()`] gives the minimal edits to make to the input instead of a new string.
+//! [`format_partial()`] formats the valid parts of input that contains errors.
 
 use tracing::debug;
 use typst::syntax::{parse, SyntaxKind, SyntaxNode};
 
 mod config;
+mod diagnostic;
+mod edits;
+mod equivalence;
+mod partial;
+mod range;
 mod render;
 mod writer;
 
-pub use config::Config;
+pub use config::{Config, EnumMarker, IndentStyle, MarkupWrap, NewlineStyle, TrailingComma};
+use diagnostic::{diagnostics, first_difference};
+pub use diagnostic::{Diagnostic, Difference};
+pub use edits::{format_edits, TextEdit};
+use equivalence::check_equivalent;
+pub use partial::{format_partial, PartialFormat};
+pub use range::{format_range, FormattedRange};
 use render::Renderer;
 use writer::Writer;
 
@@ -23,13 +37,32 @@ use writer::Writer;
 pub enum FormatError {
     /// Invalid code was given, we don't try to format erroneous things.
     #[error("The input contained errors, not formatting")]
-    ErroneousInput,
+    ErroneousInput(Vec<Diagnostic>),
     /// The formatter produced an invalid output, not letting it get written out.
     #[error("An internal error produced an erroneous output")]
-    ProducedErroneousOutput,
+    ProducedErroneousOutput {
+        /// The errors in the output.
+        errors: Vec<Diagnostic>,
+        /// The first change made to the input.
+        difference: Option<Difference>,
+    },
+    /// The formatter changed what the code means, not letting it get written out.
+    #[error("An internal error changed the meaning of the input")]
+    ChangedMeaning {
+        /// Where the input first differs from the output.
+        before: Box<Diagnostic>,
+        /// Where the output first differs from the input.
+        after: Box<Diagnostic>,
+    },
     /// The formatter failed to find a fixed point, formatting again will change it.
     #[error("Failed to find fixed point, reformat will not pass check")]
-    FailedToFindFixedPoint,
+    FailedToFindFixedPoint {
+        /// The first change made by formatting the output again.
+        difference: Option<Difference>,
+    },
+    /// The range to format was out of bounds or not on character boundaries.
+    #[error("The range to format is not valid for the input")]
+    InvalidRange,
 }
 
 /// Check the parsed tree for error nodes, not relying on the in-built `erroneous` field/function.
@@ -51,38 +84,41 @@ pub fn format(input: &str, config: Config) -> Result<String, FormatError> {
     // don't try to format things that aren't valid
     if erroneous(&init) {
         debug!(?init, "Not formatting erroneous input");
-        let errors = init.errors();
-        for error in errors {
-            debug!(?error, "error");
-        }
-        return Err(FormatError::ErroneousInput);
+        return Err(FormatError::ErroneousInput(diagnostics(&init, input)));
     }
     debug!("parsed: {init:?}");
+    let config = Config {
+        newline_style: config.newline_style.resolve(input),
+        ..config
+    };
     let writer = Writer::new(config.clone());
 
-    let mut renderer = Renderer { writer };
-    renderer.render(init);
+    let mut renderer = Renderer::new(writer);
+    renderer.render(init.clone());
 
     let output = renderer.finish();
 
     let reparsed = parse(&output);
     if erroneous(&reparsed) {
         debug!(?output, "Formatted text contained errors!");
-        let errors = reparsed.errors();
-        for error in errors {
-            debug!(?error, "error");
-        }
-        return Err(FormatError::ProducedErroneousOutput);
+        return Err(FormatError::ProducedErroneousOutput {
+            errors: diagnostics(&reparsed, &output),
+            difference: first_difference(input, &output),
+        });
     }
 
+    check_equivalent(&init, input, &reparsed, &output, config.enum_marker)?;
+
     debug!(?output, "checking for fixed point");
     let writer2 = Writer::new(config);
-    let mut renderer2 = Renderer { writer: writer2 };
+    let mut renderer2 = Renderer::new(writer2);
     renderer2.render(reparsed);
     let output2 = renderer2.finish();
     if output != output2 {
         debug!(?output, ?output2, "Formatted text would not pass check");
-        return Err(FormatError::FailedToFindFixedPoint);
+        return Err(FormatError::FailedToFindFixedPoint {
+            difference: first_difference(&output, &output2),
+        });
     }
 
     Ok(output)
@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use lsp_server::Connection;
+use lsp_server::Message;
+use lsp_server::Notification;
+use lsp_server::Request;
+use lsp_server::Response;
+use lsp_types::notification::DidChangeTextDocument;
+use lsp_types::notification::DidCloseTextDocument;
+use lsp_types::notification::DidOpenTextDocument;
+use lsp_types::notification::Notification as _;
+use lsp_types::request::Formatting;
+use lsp_types::request::OnTypeFormatting;
+use lsp_types::request::RangeFormatting;
+use lsp_types::request::Request as _;
+use lsp_types::DocumentOnTypeFormattingOptions;
+use lsp_types::InitializeParams;
+use lsp_types::OneOf;
+use lsp_types::Position;
+use lsp_types::ServerCapabilities;
+use lsp_types::TextDocumentSyncCapability;
+use lsp_types::TextDocumentSyncKind;
+use lsp_types::TextEdit;
+use lsp_types::Url;
+use tracing::debug;
+use tracing::info;
+use tracing::warn;
+use typstfmt::format_edits;
+use typstfmt::format_range;
+use typstfmt::Config;
+
+use crate::discovery::ConfigResolver;
+
+/// Run the language server over stdio until the client asks it to shut down.
+pub fn run(config_path: Option<&Path>) -> anyhow::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(&connection, config_path)?;
+
+    // the io threads only finish once the connection is closed
+    drop(connection);
+    io_threads.join()?;
+    info!("Shut down language server");
+    Ok(())
+}
+
+/// Answer the client at the other end of the connection until it asks the server to shut down.
+fn serve(connection: &Connection, config_path: Option<&Path>) -> anyhow::Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_range_formatting_provider: Some(OneOf::Left(true)),
+        document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+            first_trigger_character: "}".to_owned(),
+            more_trigger_character: Some(vec!["]".to_owned(), ")".to_owned()]),
+        }),
+        ..Default::default()
+    };
+    let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    let root = workspace_root(&params).unwrap_or_else(|| PathBuf::from("."));
+    let configs = ConfigResolver::new(config_path.map(|path| root.join(path)).as_deref())?;
+    info!(?root, "Started language server");
+
+    let mut server = Server {
+        root,
+        configs,
+        documents: HashMap::new(),
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                let response = server.handle_request(request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => server.handle_notification(notification),
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+#[allow(deprecated)]
+fn workspace_root(params: &InitializeParams) -> Option<PathBuf> {
+    params
+        .workspace_folders
+        .as_ref()
+        .and_then(|folders| folders.first())
+        .map(|folder| &folder.uri)
+        .or(params.root_uri.as_ref())
+        .and_then(|uri| uri.to_file_path().ok())
+}
+
+struct Server {
+    /// The root of the workspace, for finding the config of documents that aren't files.
+    root: PathBuf,
+    configs: ConfigResolver,
+    /// The current content of the open documents.
+    documents: HashMap<Url, String>,
+}
+
+impl Server {
+    fn handle_request(&mut self, request: Request) -> Response {
+        debug!(method = request.method, "Handling request");
+        let id = request.id.clone();
+        let edits = match request.method.as_str() {
+            Formatting::METHOD => match extract::<Formatting>(request) {
+                Ok(params) => self.format_document(&params.text_document.uri),
+                Err(response) => return response,
+            },
+            RangeFormatting::METHOD => match extract::<RangeFormatting>(request) {
+                Ok(params) => self.format_range(
+                    &params.text_document.uri,
+                    params.range.start,
+                    params.range.end,
+                ),
+                Err(response) => return response,
+            },
+            OnTypeFormatting::METHOD => match extract::<OnTypeFormatting>(request) {
+                Ok(params) => {
+                    let position = params.text_document_position.position;
+                    let uri = &params.text_document_position.text_document.uri;
+                    // format around the character that was just typed
+                    let start = Position {
+                        line: position.line,
+                        character: position.character.saturating_sub(1),
+                    };
+                    self.format_range(uri, start, position)
+                }
+                Err(response) => return response,
+            },
+            method => {
+                return Response::new_err(
+                    id,
+                    lsp_server::ErrorCode::MethodNotFound as i32,
+                    format!("unhandled method {method}"),
+                )
+            }
+        };
+        Response::new_ok(id, edits)
+    }
+
+    fn handle_notification(&mut self, notification: Notification) {
+        debug!(method = notification.method, "Handling notification");
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                if let Ok(params) = notification
+                    .extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)
+                {
+                    self.documents
+                        .insert(params.text_document.uri, params.text_document.text);
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                if let Ok(params) = notification.extract::<lsp_types::DidChangeTextDocumentParams>(
+                    DidChangeTextDocument::METHOD,
+                ) {
+                    // we only ask for full syncs so the last change has the whole content
+                    if let Some(change) = params.content_changes.into_iter().last() {
+                        self.documents.insert(params.text_document.uri, change.text);
+                    }
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                if let Ok(params) = notification
+                    .extract::<lsp_types::DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)
+                {
+                    self.documents.remove(&params.text_document.uri);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The config to format the document with.
+    fn config(&mut self, uri: &Url) -> Option<Config> {
+        let result = match uri.to_file_path() {
+            Ok(path) => self.configs.resolve(&path),
+            Err(()) => self.configs.resolve_dir(&self.root),
+        };
+        match result {
+            Ok(resolved) => Some(resolved.config),
+            Err(error) => {
+                warn!(%uri, %error, "Failed to get config");
+                None
+            }
+        }
+    }
+
+    fn format_document(&mut self, uri: &Url) -> Option<Vec<TextEdit>> {
+        let config = self.config(uri)?;
+        let content = self.documents.get(uri)?;
+        match format_edits(content, &config) {
+            Ok(edits) => Some(
+                edits
+                    .into_iter()
+                    .map(|edit| TextEdit {
+                        range: lsp_types::Range {
+                            start: offset_to_position(content, edit.range.start),
+                            end: offset_to_position(content, edit.range.end),
+                        },
+                        new_text: edit.text,
+                    })
+                    .collect(),
+            ),
+            Err(error) => {
+                warn!(%uri, %error, "Failed to format document");
+                None
+            }
+        }
+    }
+
+    fn format_range(&mut self, uri: &Url, start: Position, end: Position) -> Option<Vec<TextEdit>> {
+        let config = self.config(uri)?;
+        let content = self.documents.get(uri)?;
+        let start = position_to_offset(content, start)?;
+        let end = position_to_offset(content, end)?;
+        match format_range(content, start..end, config) {
+            Ok(formatted) if content[formatted.range.clone()] != formatted.text => {
+                Some(vec![TextEdit {
+                    range: lsp_types::Range {
+                        start: offset_to_position(content, formatted.range.start),
+                        end: offset_to_position(content, formatted.range.end),
+                    },
+                    new_text: formatted.text,
+                }])
+            }
+            Ok(_) => Some(Vec::new()),
+            Err(error) => {
+                warn!(%uri, %error, "Failed to format range");
+                None
+            }
+        }
+    }
+}
+
+fn extract<R>(request: Request) -> Result<R::Params, Response>
+where
+    R: lsp_types::request::Request,
+{
+    let id = request.id.clone();
+    request
+        .extract(R::METHOD)
+        .map(|(_, params)| params)
+        .map_err(|error| {
+            Response::new_err(
+                id,
+                lsp_server::ErrorCode::InvalidParams as i32,
+                format!("{error:?}"),
+            )
+        })
+}
+
+/// Convert a byte offset in the text to an LSP position, which counts UTF-16 code units.
+fn offset_to_position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character = before[line_start..].encode_utf16().count();
+    Position::new(line as u32, character as u32)
+}
+
+/// Convert an LSP position to a byte offset in the text, if it is within the text.
+fn position_to_offset(text: &str, position: Position) -> Option<usize> {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        line_start += text[line_start..].find('\n')? + 1;
+    }
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (index, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return Some(line_start + index);
+        }
+        units += c.len_utf16();
+    }
+    Some(line_start + line.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use lsp_server::RequestId;
+    use lsp_types::notification::Exit;
+    use lsp_types::notification::Initialized;
+    use lsp_types::request::Initialize;
+    use lsp_types::request::Shutdown;
+    use lsp_types::DidOpenTextDocumentParams;
+    use lsp_types::DocumentFormattingParams;
+    use lsp_types::DocumentRangeFormattingParams;
+    use lsp_types::TextDocumentIdentifier;
+    use lsp_types::TextDocumentItem;
+
+    use super::*;
+
+    /// A server running on the other end of an in-memory connection, with one open document.
+    struct Client {
+        connection: Connection,
+        server: std::thread::JoinHandle<anyhow::Result<()>>,
+        root: PathBuf,
+        uri: Url,
+        text: String,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn open(name: &str, text: &str) -> Client {
+            let root =
+                std::env::temp_dir().join(format!("typstfmt-lsp-{name}-{}", std::process::id()));
+            fs::create_dir_all(&root).unwrap();
+            let (connection, server) = Connection::memory();
+            let server = std::thread::spawn(move || serve(&server, None));
+            let mut client = Client {
+                connection,
+                server,
+                uri: Url::from_file_path(root.join("doc.typ")).unwrap(),
+                root,
+                text: text.to_owned(),
+                next_id: 0,
+            };
+
+            let params = InitializeParams {
+                workspace_folders: Some(vec![lsp_types::WorkspaceFolder {
+                    uri: Url::from_directory_path(&client.root).unwrap(),
+                    name: name.to_owned(),
+                }]),
+                ..Default::default()
+            };
+            client.request::<Initialize>(params);
+            client.notify::<Initialized>(lsp_types::InitializedParams {});
+            client.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    client.uri.clone(),
+                    "typst".to_owned(),
+                    0,
+                    client.text.clone(),
+                ),
+            });
+            client
+        }
+
+        fn request<R: lsp_types::request::Request>(&mut self, params: R::Params) -> R::Result {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            let request = Request::new(id.clone(), R::METHOD.to_owned(), params);
+            self.connection.sender.send(request.into()).unwrap();
+            match self.connection.receiver.recv().unwrap() {
+                Message::Response(response) if response.id == id => {
+                    serde_json::from_value(response.result.unwrap()).unwrap()
+                }
+                message => panic!("expected a response to {}, got {message:?}", R::METHOD),
+            }
+        }
+
+        fn notify<N: lsp_types::notification::Notification>(&self, params: N::Params) {
+            let notification = Notification::new(N::METHOD.to_owned(), params);
+            self.connection.sender.send(notification.into()).unwrap();
+        }
+
+        /// Apply the edits to the open document, from last to first.
+        fn apply(&mut self, edits: Option<Vec<TextEdit>>) {
+            for edit in edits.unwrap().iter().rev() {
+                let start = position_to_offset(&self.text, edit.range.start).unwrap();
+                let end = position_to_offset(&self.text, edit.range.end).unwrap();
+                self.text.replace_range(start..end, &edit.new_text);
+            }
+        }
+
+        fn shut_down(mut self) -> String {
+            self.request::<Shutdown>(());
+            self.notify::<Exit>(());
+            self.server.join().unwrap().unwrap();
+            fs::remove_dir_all(&self.root).unwrap();
+            self.text
+        }
+    }
+
+    #[test]
+    fn formatting_round_trip() {
+        let mut client = Client::open("formatting", "#f(a,b)\n#g( c )\n");
+        let edits = client.request::<Formatting>(DocumentFormattingParams {
+            text_document: TextDocumentIdentifier::new(client.uri.clone()),
+            options: Default::default(),
+            work_done_progress_params: Default::default(),
+        });
+        client.apply(edits);
+        assert_eq!(client.shut_down(), "#f(a, b)\n#g(c)\n");
+    }
+
+    #[test]
+    fn range_formatting_round_trip() {
+        let mut client = Client::open("range-formatting", "#f(a,b)\n#g( c )\n");
+        let edits = client.request::<RangeFormatting>(DocumentRangeFormattingParams {
+            text_document: TextDocumentIdentifier::new(client.uri.clone()),
+            range: lsp_types::Range::new(Position::new(1, 2), Position::new(1, 6)),
+            options: Default::default(),
+            work_done_progress_params: Default::default(),
+        });
+        client.apply(edits);
+        assert_eq!(client.shut_down(), "#f(a,b)\n#g(c)\n");
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        let text = "#let x = 1\n$α + β$ = 2\n";
+        for offset in [0, 5, 11, 14, 17, text.len()] {
+            let position = offset_to_position(text, offset);
+            assert_eq!(position_to_offset(text, position), Some(offset));
+        }
+        assert_eq!(offset_to_position(text, 17), Position::new(1, 5));
+    }
+
+    #[test]
+    fn position_past_end_of_line() {
+        let text = "ab\ncd";
+        assert_eq!(position_to_offset(text, Position::new(0, 10)), Some(2));
+        assert_eq!(position_to_offset(text, Position::new(5, 0)), None);
+    }
+}
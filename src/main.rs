@@ -11,10 +11,28 @@ use tracing::metadata::LevelFilter;
 use tracing::warn;
 use tracing_subscriber::EnvFilter;
 use typstfmt::format;
+use typstfmt::format_partial;
 use typstfmt::Config;
 use typstfmt::FormatError;
 
+use cache::Cache;
+use discovery::ConfigResolver;
+use output::FileReport;
+use output::OutputFormat;
+use output::Status;
+use output::Summary;
+
 use clap::Parser;
+use clap::Subcommand;
+use rayon::iter::IntoParallelIterator;
+use rayon::iter::ParallelIterator;
+
+mod cache;
+mod discovery;
+mod lsp;
+mod output;
+mod report;
+mod walk;
 
 #[derive(Debug, thiserror::Error)]
 enum Error {
@@ -26,16 +44,58 @@ enum Error {
     IO(#[from] std::io::Error),
     #[error("toml config error: {0}")]
     Toml(#[from] toml::de::Error),
+    #[error("config file not found: {}", .0.display())]
+    MissingConfig(PathBuf),
 }
 
 #[derive(Parser, Debug)]
-#[command(version = "0.1.0", about = "Format typst code")]
+#[command(
+    version = "0.1.0",
+    about = "Format typst code",
+    args_conflicts_with_subcommands = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// A file or directory to format. If not specified, stdin is read for input.
     files: Vec<PathBuf>,
 
-    #[arg(long, default_value = "typstfmt.toml")]
-    config_path: PathBuf,
+    /// The config file to use for every input. If not specified, each input uses the nearest
+    /// `typstfmt.toml` or `.typstfmt.toml` in its directory or the directories above it.
+    #[arg(long, global = true)]
+    config_path: Option<PathBuf>,
+
+    /// A glob pattern of files and directories to skip when walking directories, in addition to
+    /// the config's `exclude` patterns.
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// The number of files to format at once. Defaults to the number of CPUs.
+    #[arg(long, short, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Format every file, rather than skipping those the cache knows are already formatted.
+    #[arg(long)]
+    no_cache: bool,
+
+    /// The directory to keep the cache in. Defaults to a `typstfmt` directory in the user's cache
+    /// directory.
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// How to report the results.
+    #[arg(long, value_enum, default_value_t)]
+    output_format: OutputFormat,
+
+    /// The path of the file that stdin is the content of, to find its config and whether it is
+    /// excluded by. Also used to name it in the output.
+    #[arg(long, value_name = "PATH")]
+    stdin_filepath: Option<PathBuf>,
+
+    /// Print the config that would be used to format the given file, then exit.
+    #[arg(long, value_name = "FILE")]
+    print_config: Option<PathBuf>,
 
     /// Run in 'check' mode. Exits with 0 if all input is formatted correctly. Exits with 1 if formatting of any input is required.
     #[arg(long)]
@@ -45,120 +105,166 @@ struct Args {
     /// Also behaves like 'check' mode for exit codes.
     #[arg(long)]
     diff: bool,
+
+    /// Format the valid parts of inputs that contain errors, leaving the erroneous parts as they
+    /// are.
+    #[arg(long)]
+    partial: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a language server over stdio, for editors to format documents with.
+    Lsp,
 }
 
 fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::builder()
-                .with_default_directive(LevelFilter::INFO.into())
-                .from_env_lossy(),
-        )
-        .init();
-
-    let paths: Vec<_> = if !args.files.is_empty() {
-        args.files
-            .iter()
-            .flat_map(|f| {
-                if f.is_file() {
-                    vec![f.to_owned()]
-                } else {
-                    globmatch::Builder::new("**/*.typ")
-                        .build(f)
-                        .unwrap()
-                        .into_iter()
-                        .filter_map(|p| p.ok())
-                        .collect()
-                }
-            })
-            .collect()
-    } else {
-        Vec::new()
-    };
+    let subscriber = tracing_subscriber::fmt().with_env_filter(
+        EnvFilter::builder()
+            .with_default_directive(LevelFilter::INFO.into())
+            .from_env_lossy(),
+    );
+
+    if let Some(Command::Lsp) = args.command {
+        // stdout is used for the protocol itself
+        subscriber.with_writer(std::io::stderr).init();
+        return lsp::run(args.config_path.as_deref());
+    }
 
-    let config = if args.config_path.is_file() {
-        debug!(config_path=?args.config_path, "Loading config from file");
-        let mut config_file = File::open(&args.config_path)?;
-        let mut config_file_content = String::new();
-        config_file.read_to_string(&mut config_file_content)?;
-        toml::from_str(&config_file_content)?
+    if args.output_format == OutputFormat::Json || writes_stdin(&args) {
+        // stdout is used for the report or the formatted text
+        subscriber.with_writer(std::io::stderr).init();
     } else {
-        debug!("Using default config");
-        typstfmt::Config::default()
-    };
+        subscriber.init();
+    }
+
+    let mut configs = ConfigResolver::new(args.config_path.as_deref())?;
+
+    if let Some(path) = &args.print_config {
+        let resolved = configs.resolve(path)?;
+        match &resolved.path {
+            Some(config_path) => println!("# loaded from {}", config_path.display()),
+            None => println!("# default config"),
+        }
+        print!("{}", toml::to_string(&resolved.config)?);
+        print!("{}", toml::to_string(&resolved.walk)?);
+        return Ok(());
+    }
+
+    let mut paths = Vec::new();
+    for file in &args.files {
+        if file.is_file() {
+            // files given explicitly are always formatted
+            paths.push(file.to_owned());
+        } else {
+            let walk = configs.resolve_dir(file)?.walk;
+            paths.extend(walk::typst_files(file, &walk, &args.exclude)?);
+        }
+    }
 
     let start = Instant::now();
 
-    let mut formatted = 0;
-    let mut unchanged = 0;
-    let mut erroneous = 0;
-    if paths.is_empty() {
+    let mut summary = Summary::default();
+    if args.files.is_empty() {
         // read from stdin
         let input = std::io::read_to_string(stdin())?;
-        match format_stdin(&input, &config, &args) {
-            Ok(did_format) => match did_format {
-                DidFormat::Yes => {
-                    info!("Successfully formatted stdin");
-                    formatted += 1;
-                }
-                DidFormat::No => {
-                    info!("Already correctly formatted");
-                    unchanged += 1;
-                }
-            },
-            Err(error) => match error {
-                Error::Format(_) => {
-                    warn!(%error, "Failed to format stdin");
-                    erroneous += 1;
-                }
-                Error::CheckFailed => {
-                    warn!("Failed check");
-                    formatted += 1;
-                }
-                Error::IO(_) => {
-                    warn!(%error, "Got an error")
-                }
-                Error::Toml(_) => {
-                    warn!(%error, "Failed to get config")
-                }
-            },
-        }
+        let cwd = std::env::current_dir()?;
+        let resolved = match &args.stdin_filepath {
+            Some(path) => configs.resolve(path),
+            None => configs.resolve_dir(&cwd),
+        };
+        // the file stdin is for is left as it is if it wouldn't be formatted in a directory
+        let excluded = match (&args.stdin_filepath, &resolved) {
+            (Some(path), Ok(resolved)) => {
+                walk::is_excluded(&cwd, &cwd.join(path), &resolved.walk, &args.exclude)?
+            }
+            _ => false,
+        };
+        let name = args
+            .stdin_filepath
+            .as_ref()
+            .map_or("stdin", |path| path.to_str().unwrap());
+        let mut deferred = Deferred::default();
+        let result = resolved.and_then(|resolved| {
+            format_stdin(
+                &input,
+                &resolved.config,
+                &args,
+                name,
+                excluded,
+                &mut deferred,
+            )
+        });
+        record(&mut summary, name, result, deferred, &args);
     } else {
-        for path in paths.into_iter() {
-            match format_file(&path, &config, &args) {
-                Ok(did_format) => match did_format {
-                    DidFormat::Yes => {
-                        info!(?path, "Successfully formatted file");
-                        formatted += 1;
-                    }
-                    DidFormat::No => {
-                        info!(?path, "Already correctly formatted");
-                        unchanged += 1;
-                    }
-                },
-                Err(error) => match error {
-                    Error::Format(_) => {
-                        warn!(?path, %error, "Failed to format file");
-                        erroneous += 1;
-                    }
-                    Error::CheckFailed => {
-                        warn!(?path, "Failed check");
-                        formatted += 1;
-                    }
-                    Error::IO(_) => {
-                        warn!(?path, %error, "Got an error")
-                    }
-                    Error::Toml(_) => {
-                        warn!(?path, %error, "Failed to get config")
-                    }
-                },
+        // configs are cached as they are found, so find them all before formatting in parallel
+        let inputs: Vec<_> = paths
+            .into_iter()
+            .map(|path| {
+                let config = configs.resolve(&path).map(|resolved| resolved.config);
+                (path, config)
+            })
+            .collect();
+        let mut cache = if args.no_cache {
+            None
+        } else {
+            args.cache_dir
+                .clone()
+                .or_else(cache::default_cache_dir)
+                .map(|dir| Cache::load(&dir))
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(args.jobs.unwrap_or(0))
+            .build()?;
+        // the results are collected in the order of the inputs, and only reported once they are
+        // all done so that the output is the same however many jobs there are
+        let outcomes: Vec<_> = pool.install(|| {
+            inputs
+                .into_par_iter()
+                .map(|(path, config)| {
+                    let mut deferred = Deferred::default();
+                    let result = config.and_then(|config| {
+                        format_file(&path, &config, &args, cache.as_ref(), &mut deferred)
+                    });
+                    (path, deferred, result)
+                })
+                .collect()
+        });
+
+        for (path, mut deferred, result) in outcomes {
+            if let (Some(cache), Some(key)) = (&mut cache, deferred.cache_key.take()) {
+                cache.insert(&path, key);
+            }
+            record(
+                &mut summary,
+                path.to_str().unwrap(),
+                result,
+                deferred,
+                &args,
+            );
+        }
+
+        if let Some(cache) = &cache {
+            if let Err(error) = cache.save() {
+                warn!(%error, "Failed to save the cache");
             }
         }
     }
 
-    let elapsed = start.elapsed();
+    summary.elapsed = start.elapsed();
+    let Summary {
+        formatted,
+        unchanged,
+        erroneous,
+        elapsed,
+        ..
+    } = summary;
+
+    if args.output_format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    }
 
     if args.check {
         if formatted > 0 {
@@ -168,13 +274,18 @@ fn main() -> anyhow::Result<()> {
                 erroneous,
                 formatted
             );
-        } else {
+        } else if args.output_format == OutputFormat::Text {
             println!(
                 "Passed check. {} files passed, {} had errors, {:?}.",
                 unchanged, erroneous, elapsed
             );
         }
-    } else {
+    } else if writes_stdin(&args) {
+        eprintln!(
+            "{} files formatted. {} files were already correct. {} files had errors, {:?}.",
+            formatted, unchanged, erroneous, elapsed
+        );
+    } else if args.output_format == OutputFormat::Text {
         println!(
             "{} files formatted. {} files were already correct. {} files had errors, {:?}.",
             formatted, unchanged, erroneous, elapsed
@@ -184,12 +295,127 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Whether the formatted text of stdin gets written to stdout.
+fn writes_stdin(args: &Args) -> bool {
+    args.files.is_empty() && !args.check && !args.diff && args.output_format == OutputFormat::Text
+}
+
+/// Log the result of formatting an input, and add it to the summary.
+fn record(
+    summary: &mut Summary,
+    name: &str,
+    result: Result<DidFormat, Error>,
+    deferred: Deferred,
+    args: &Args,
+) {
+    for line in &deferred.skipped_lines {
+        warn!(name, line, "Left erroneous code unformatted");
+    }
+    if let Some(diff) = &deferred.diff {
+        // the JSON report has the hunks instead
+        if args.output_format == OutputFormat::Text {
+            println!("{diff}");
+        }
+    }
+
+    let mut file = FileReport {
+        path: name.to_owned(),
+        status: Status::Unchanged,
+        error: None,
+        diagnostics: Vec::new(),
+        hunks: deferred.hunks,
+        output: deferred.output,
+    };
+    match result {
+        Ok(DidFormat::Yes) => {
+            info!(name, "Successfully formatted");
+            summary.formatted += 1;
+            file.status = Status::Formatted;
+        }
+        Ok(DidFormat::No) => {
+            info!(name, "Already correctly formatted");
+            summary.unchanged += 1;
+        }
+        Err(error) => {
+            match &error {
+                Error::Format(format_error) => {
+                    report::report(name, format_error);
+                    warn!(name, %error, "Failed to format");
+                    summary.erroneous += 1;
+                    file.status = Status::Erroneous;
+                    file.diagnostics = output::diagnostics(format_error);
+                }
+                Error::CheckFailed => {
+                    warn!(name, "Failed check");
+                    summary.formatted += 1;
+                    file.status = Status::Formatted;
+                }
+                Error::IO(_) => {
+                    warn!(name, %error, "Got an error");
+                    file.status = Status::IoError;
+                }
+                Error::Toml(_) | Error::MissingConfig(_) => {
+                    warn!(name, %error, "Failed to get config");
+                    file.status = Status::ConfigError;
+                }
+            }
+            if !matches!(error, Error::CheckFailed) {
+                file.error = Some(error.to_string());
+            }
+        }
+    }
+    summary.files.push(file);
+}
+
+/// The results about an input that are held back until all inputs are done, so that it comes out in
+/// the same order however many jobs there are.
+#[derive(Default)]
+struct Deferred {
+    /// The lines of erroneous code left unformatted in 'partial' mode.
+    skipped_lines: Vec<usize>,
+    /// The diff to print in 'diff' mode.
+    diff: Option<String>,
+    /// The changes made by formatting, for the JSON report.
+    hunks: Vec<String>,
+    /// The formatted text of stdin, for the JSON report.
+    output: Option<String>,
+    /// The key to remember the file by in the cache, once it is known to be formatted.
+    cache_key: Option<String>,
+}
+
+/// Format the content, only the valid parts of it if running in 'partial' mode.
+fn format_content(
+    content: &str,
+    config: &Config,
+    args: &Args,
+    deferred: &mut Deferred,
+) -> Result<String, Error> {
+    if args.partial {
+        // TODO: remove this clone, format should take a &Config
+        let formatted = format_partial(content, config.clone())?;
+        for range in formatted.skipped {
+            let line = content[..range.start].matches('\n').count() + 1;
+            deferred.skipped_lines.push(line);
+        }
+        Ok(formatted.output)
+    } else {
+        // TODO: remove this clone, format should take a &Config
+        Ok(format(content, config.clone())?)
+    }
+}
+
 enum DidFormat {
     Yes,
     No,
 }
 
-fn format_file(path: &Path, config: &Config, args: &Args) -> Result<DidFormat, Error> {
+fn format_file(
+    path: &Path,
+    config: &Config,
+    args: &Args,
+    cache: Option<&Cache>,
+    deferred: &mut Deferred,
+) -> Result<DidFormat, Error> {
     let mut file = File::options()
         .read(true)
         .open(path)
@@ -198,10 +424,14 @@ fn format_file(path: &Path, config: &Config, args: &Args) -> Result<DidFormat, E
     file.read_to_string(&mut content)?;
     drop(file);
 
+    if cache.is_some_and(|cache| cache.is_formatted(path, &Cache::key(config, &content))) {
+        debug!(?path, "Skipping input known to be formatted");
+        return Ok(DidFormat::No);
+    }
+
     debug!(?path, "Formatting input");
 
-    // TODO: remove this clone, format should take a &Config
-    let formatted = format(&content, config.clone())?;
+    let formatted = format_content(&content, config, args, deferred)?;
 
     let did_format = if formatted == content {
         DidFormat::No
@@ -209,14 +439,26 @@ fn format_file(path: &Path, config: &Config, args: &Args) -> Result<DidFormat, E
         DidFormat::Yes
     };
 
+    if args.output_format == OutputFormat::Json {
+        deferred.hunks = hunks(&content, &formatted);
+    }
+
+    // files with erroneous parts aren't formatted, so have to be checked every time
+    let cacheable = cache.is_some() && deferred.skipped_lines.is_empty();
+    if cacheable && matches!(did_format, DidFormat::No) {
+        deferred.cache_key = Some(Cache::key(config, &content));
+    }
+
     if args.diff {
         let text_diff = similar::TextDiff::from_lines(&content, &formatted);
-        println!(
-            "{}",
-            text_diff.unified_diff().header(
-                path.to_str().unwrap(),
-                &format!("{}.formatted", path.to_str().unwrap())
-            )
+        deferred.diff = Some(
+            text_diff
+                .unified_diff()
+                .header(
+                    path.to_str().unwrap(),
+                    &format!("{}.formatted", path.to_str().unwrap()),
+                )
+                .to_string(),
         );
         if matches!(did_format, DidFormat::Yes) {
             return Err(Error::CheckFailed);
@@ -232,15 +474,28 @@ fn format_file(path: &Path, config: &Config, args: &Args) -> Result<DidFormat, E
     } else if matches!(did_format, DidFormat::Yes) {
         let mut file = File::create(path)?;
         file.write_all(formatted.as_bytes())?;
+        if cacheable {
+            deferred.cache_key = Some(Cache::key(config, &formatted));
+        }
     }
     Ok(did_format)
 }
 
-fn format_stdin(content: &str, config: &Config, args: &Args) -> Result<DidFormat, Error> {
-    debug!("Formatting stdin");
-
-    // TODO: remove this clone, format should take a &Config
-    let formatted = format(content, config.clone())?;
+fn format_stdin(
+    content: &str,
+    config: &Config,
+    args: &Args,
+    name: &str,
+    excluded: bool,
+    deferred: &mut Deferred,
+) -> Result<DidFormat, Error> {
+    let formatted = if excluded {
+        debug!(name, "Not formatting excluded input");
+        content.to_owned()
+    } else {
+        debug!(name, "Formatting stdin");
+        format_content(content, config, args, deferred)?
+    };
 
     let did_format = if formatted == content {
         DidFormat::No
@@ -248,13 +503,17 @@ fn format_stdin(content: &str, config: &Config, args: &Args) -> Result<DidFormat
         DidFormat::Yes
     };
 
+    if args.output_format == OutputFormat::Json {
+        deferred.hunks = hunks(content, &formatted);
+    }
+
     if args.diff {
         let text_diff = similar::TextDiff::from_lines(content, &formatted);
-        println!(
-            "{}",
+        deferred.diff = Some(
             text_diff
                 .unified_diff()
-                .header("stdin", &format!("{}.formatted", "stdin"))
+                .header(name, &format!("{}.formatted", name))
+                .to_string(),
         );
         if matches!(did_format, DidFormat::Yes) {
             return Err(Error::CheckFailed);
@@ -267,8 +526,21 @@ fn format_stdin(content: &str, config: &Config, args: &Args) -> Result<DidFormat
         if matches!(did_format, DidFormat::Yes) {
             return Err(Error::CheckFailed);
         }
-    } else if matches!(did_format, DidFormat::Yes) {
-        println!("{}", formatted);
+    } else if args.output_format == OutputFormat::Json {
+        // stdout is taken by the report
+        deferred.output = Some(formatted);
+    } else {
+        // always write the text out, as whatever is reading it uses it in place of the input
+        print!("{}", formatted);
     }
     Ok(did_format)
 }
+
+/// The unified diff hunks that turn `old` into `new`.
+fn hunks(old: &str, new: &str) -> Vec<String> {
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .iter_hunks()
+        .map(|hunk| hunk.to_string())
+        .collect()
+}
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use typstfmt::Diagnostic;
+use typstfmt::FormatError;
+
+/// How to report the results of formatting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Log each input as it is reported, then a sentence summing them up.
+    #[default]
+    Text,
+    /// A single JSON object with the status of each input and the totals.
+    Json,
+}
+
+/// What happened to an input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    /// The input needed formatting, and was formatted unless only checking.
+    Formatted,
+    /// The input was already formatted.
+    Unchanged,
+    /// The input couldn't be formatted.
+    Erroneous,
+    /// The input couldn't be read or written.
+    IoError,
+    /// The config for the input couldn't be loaded.
+    ConfigError,
+}
+
+/// The result of formatting one input.
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub path: String,
+    pub status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Where the input has errors or was changed in meaning.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
+    /// The changes formatting made, as unified diff hunks.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hunks: Vec<String>,
+    /// The formatted text, for stdin as it has nowhere else to be written to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// The results of formatting all the inputs.
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub files: Vec<FileReport>,
+    pub formatted: usize,
+    pub unchanged: usize,
+    pub erroneous: usize,
+    #[serde(rename = "elapsed_ms", serialize_with = "as_millis")]
+    pub elapsed: Duration,
+}
+
+fn as_millis<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+/// The diagnostics pointing into the input, for those errors that have them.
+pub fn diagnostics(error: &FormatError) -> Vec<Diagnostic> {
+    match error {
+        FormatError::ErroneousInput(diagnostics) => diagnostics.clone(),
+        FormatError::ChangedMeaning { before, .. } => vec![(**before).clone()],
+        FormatError::ProducedErroneousOutput { .. }
+        | FormatError::FailedToFindFixedPoint { .. }
+        | FormatError::InvalidRange => Vec::new(),
+    }
+}
@@ -0,0 +1,81 @@
+use std::ops::Range;
+
+use tracing::debug;
+use typst::syntax::{parse, SyntaxNode};
+
+use crate::diagnostic::{diagnostics, first_difference};
+use crate::equivalence::check_equivalent;
+use crate::render::Renderer;
+use crate::writer::Writer;
+use crate::{erroneous, Config, FormatError};
+
+/// The result of formatting input that may contain errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialFormat {
+    /// The formatted text, with the erroneous parts as they were written.
+    pub output: String,
+    /// The byte ranges of the input that contained errors and so were left unformatted.
+    pub skipped: Vec<Range<usize>>,
+}
+
+/// Format the valid parts of some typst code, even if other parts of it contain errors.
+///
+/// Each top-level node of the document that contains an error is copied through unchanged, while
+/// the rest is formatted as with [`format()`](crate::format).
+pub fn format_partial(input: &str, config: Config) -> Result<PartialFormat, FormatError> {
+    debug!("input: {input:?}");
+    let init = parse(input);
+
+    let mut skipped = Vec::new();
+    let mut offset = 0;
+    for child in init.children() {
+        if erroneous(child) {
+            skipped.push(offset..offset + child.len());
+        }
+        offset += child.len();
+    }
+    debug!(?skipped, "skipping erroneous nodes");
+
+    let config = Config {
+        newline_style: config.newline_style.resolve(input),
+        ..config
+    };
+    let output = render(init.clone(), &config);
+
+    // the output may only contain the errors that were in the input
+    let reparsed = parse(&output);
+    if erroneous_texts(&reparsed) != erroneous_texts(&init) {
+        debug!(?output, "Formatted text contained new errors!");
+        return Err(FormatError::ProducedErroneousOutput {
+            errors: diagnostics(&reparsed, &output),
+            difference: first_difference(input, &output),
+        });
+    }
+
+    check_equivalent(&init, input, &reparsed, &output, config.enum_marker)?;
+
+    debug!(?output, "checking for fixed point");
+    let output2 = render(reparsed, &config);
+    if output != output2 {
+        debug!(?output, ?output2, "Formatted text would not pass check");
+        return Err(FormatError::FailedToFindFixedPoint {
+            difference: first_difference(&output, &output2),
+        });
+    }
+
+    Ok(PartialFormat { output, skipped })
+}
+
+fn render(root: SyntaxNode, config: &Config) -> String {
+    let mut renderer = Renderer::new(Writer::new(config.clone())).keep_erroneous();
+    renderer.render(root);
+    renderer.finish()
+}
+
+/// The text of each erroneous top-level node, which can only have had its line endings changed.
+fn erroneous_texts(root: &SyntaxNode) -> Vec<String> {
+    root.children()
+        .filter(|child| erroneous(child))
+        .map(|child| child.clone().into_text().replace("\r\n", "\n").to_string())
+        .collect()
+}
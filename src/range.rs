@@ -0,0 +1,163 @@
+use std::ops::Range;
+
+use tracing::debug;
+use typst::syntax::{parse, LinkedNode, SyntaxKind};
+
+use crate::diagnostic::{diagnostics, first_difference};
+use crate::equivalence::check_equivalent;
+use crate::render::Renderer;
+use crate::writer::{indentation, indentation_width, Writer};
+use crate::{erroneous, format, Config, FormatError};
+
+/// The replacement produced by formatting a range of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormattedRange {
+    /// The byte range of the input that was replaced, covering at least the requested range.
+    pub range: Range<usize>,
+    /// The formatted text to put in place of `range`.
+    pub text: String,
+}
+
+/// Format only the part of some typst code covering the given byte range.
+///
+/// The smallest syntax nodes enclosing the range get formatted, so the replaced range may be larger
+/// than the one given.
+/// Like [`format()`], the whole input must be valid typst and the result must be stable when
+/// formatted again.
+pub fn format_range(
+    input: &str,
+    range: Range<usize>,
+    config: Config,
+) -> Result<FormattedRange, FormatError> {
+    if range.start > range.end
+        || range.end > input.len()
+        || !input.is_char_boundary(range.start)
+        || !input.is_char_boundary(range.end)
+    {
+        return Err(FormatError::InvalidRange);
+    }
+
+    let init = parse(input);
+    if erroneous(&init) {
+        debug!(?init, "Not formatting erroneous input");
+        return Err(FormatError::ErroneousInput(diagnostics(&init, input)));
+    }
+
+    // the formatted part has to fit in with the line endings of the rest of the input
+    let config = Config {
+        newline_style: config.newline_style.resolve(input),
+        ..config
+    };
+    let formatted = render_range(input, &range, &config)?;
+
+    let mut output = input.to_owned();
+    output.replace_range(formatted.range.clone(), &formatted.text);
+    let reparsed = parse(&output);
+    if erroneous(&reparsed) {
+        debug!(?output, "Formatted text contained errors!");
+        return Err(FormatError::ProducedErroneousOutput {
+            errors: diagnostics(&reparsed, &output),
+            difference: first_difference(input, &output),
+        });
+    }
+
+    check_equivalent(&init, input, &reparsed, &output, config.enum_marker)?;
+
+    debug!(?output, "checking for fixed point");
+    let new_range = formatted.range.start..formatted.range.start + formatted.text.len();
+    let formatted2 = render_range(&output, &new_range, &config)?;
+    if formatted2.range != new_range || formatted2.text != formatted.text {
+        debug!(
+            ?formatted,
+            ?formatted2,
+            "Formatted range would not pass check"
+        );
+        let mut output2 = output.clone();
+        output2.replace_range(formatted2.range, &formatted2.text);
+        return Err(FormatError::FailedToFindFixedPoint {
+            difference: first_difference(&output, &output2),
+        });
+    }
+
+    Ok(formatted)
+}
+
+/// Render the smallest node that encloses the range, without any checks.
+fn render_range(
+    input: &str,
+    range: &Range<usize>,
+    config: &Config,
+) -> Result<FormattedRange, FormatError> {
+    let root = parse(input);
+    let mut node = enclosing(LinkedNode::new(&root), range);
+    while !is_standalone(&node) {
+        match node.parent() {
+            Some(parent) => node = parent.clone(),
+            None => break,
+        }
+    }
+
+    if node.parent().is_none() {
+        debug!("range covers the whole document");
+        return Ok(FormattedRange {
+            range: 0..input.len(),
+            text: format(input, config.clone())?,
+        });
+    }
+
+    let node_range = node.range();
+    debug!(?node_range, kind = ?node.kind(), "formatting node");
+
+    // continue from the content before the node on its line so that widths are accurate
+    let line_start = input[..node_range.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_prefix = &input[line_start..node_range.start];
+    let prefix = line_prefix.trim_start();
+    let indent = indentation_width(config, &line_prefix[..line_prefix.len() - prefix.len()]);
+
+    let mut writer = Writer::with_indent(config.clone(), indent);
+    writer.push_raw(prefix);
+    let mut renderer = Renderer::new(writer);
+    renderer.render_node(node.get());
+    let output = renderer.finish();
+
+    let leading = format!("{}{}", indentation(config, indent), prefix);
+    let text = output
+        .strip_prefix(&leading)
+        .ok_or(FormatError::ProducedErroneousOutput {
+            errors: Vec::new(),
+            difference: None,
+        })?;
+    let text = text
+        .strip_suffix("\r\n")
+        .or_else(|| text.strip_suffix('\n'))
+        .unwrap_or(text);
+
+    Ok(FormattedRange {
+        range: node_range,
+        text: text.to_owned(),
+    })
+}
+
+/// Find the smallest node that fully contains the range.
+fn enclosing<'a>(node: LinkedNode<'a>, range: &Range<usize>) -> LinkedNode<'a> {
+    for child in node.children() {
+        let child_range = child.range();
+        if child_range.start <= range.start && range.end <= child_range.end {
+            return enclosing(child, range);
+        }
+    }
+    node
+}
+
+/// Whether the node can be formatted on its own, without its siblings.
+fn is_standalone(node: &LinkedNode) -> bool {
+    let punctuation = matches!(
+        node.kind(),
+        SyntaxKind::Space | SyntaxKind::Parbreak | SyntaxKind::Hash | SyntaxKind::Semicolon
+    );
+    let in_sequence = matches!(
+        node.parent_kind(),
+        Some(SyntaxKind::Markup) | Some(SyntaxKind::Code)
+    );
+    !punctuation && in_sequence
+}
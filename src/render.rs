@@ -3,12 +3,10 @@ use std::unreachable;
 use tracing::debug;
 use typst::syntax::{ast::*, SyntaxKind, SyntaxNode};
 
+use crate::config::{EnumMarker, MarkupWrap, TrailingComma};
+use crate::erroneous;
 use crate::writer::Writer;
 
-fn is_multiline(children: &Children) -> bool {
-    children.any(|c| c.text().contains('\n'))
-}
-
 fn is_block(node: &SyntaxNode) -> bool {
     match node.kind() {
         SyntaxKind::Markup => false,
@@ -147,13 +145,49 @@ fn is_block(node: &SyntaxNode) -> bool {
 /// Renderer that has the information for writing out.
 pub struct Renderer {
     pub writer: Writer,
+    /// Whether groups are forced to be laid out flat, used when measuring them.
+    flat: bool,
+    /// Whether a `typstfmt: skip` directive applies to the first node of the next sequence, as
+    /// the directive came just before it.
+    skip_first: bool,
+    /// Whether erroneous top-level nodes of the document are left as they were written, rather
+    /// than formatted.
+    keep_erroneous: bool,
+    /// The width of what has to follow the node being rendered on its line, such as a content
+    /// block after arguments or a method called on the result.
+    trailing: usize,
 }
 
 impl Renderer {
+    /// Create a new renderer writing to the given writer.
+    pub fn new(writer: Writer) -> Self {
+        Self {
+            writer,
+            flat: false,
+            skip_first: false,
+            keep_erroneous: false,
+            trailing: 0,
+        }
+    }
+
+    /// Leave erroneous top-level nodes of the document as they were written, formatting only the
+    /// valid ones.
+    pub fn keep_erroneous(mut self) -> Self {
+        self.keep_erroneous = true;
+        self
+    }
+
     /// Render the AST from the given node.
     pub fn render(&mut self, node: SyntaxNode) {
         debug!(?node, "render");
-        node.cast::<Markup>().unwrap().render(self)
+        node.cast::<Markup>().unwrap().render(self);
+        self.writer.end_document();
+    }
+
+    /// Render a single node that isn't the root of the tree, such as an expression in markup.
+    pub fn render_node(&mut self, node: &SyntaxNode) {
+        debug!(?node, "render_node");
+        render_typed_or_text_2::<Markup, Expr>(node, self)
     }
 
     /// Get the rendered value.
@@ -208,12 +242,93 @@ fn render_anon(node: &SyntaxNode, renderer: &mut Renderer) {
         }
     } else {
         renderer.writer.push(node.text());
-        for child in node.children() {
-            render_anon(child, renderer)
+        let mut children = Children::new(node);
+        while let Some(child) = children.next().copied() {
+            if !render_ignored(&mut children, renderer) {
+                render_anon(child, renderer)
+            }
         }
     }
 }
 
+/// A comment controlling the formatting of the nodes that follow it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Directive {
+    /// `// typstfmt: off`, leave everything up to a `// typstfmt: on` as it is.
+    Off,
+    /// `// typstfmt: on`, resume formatting.
+    On,
+    /// `// typstfmt: skip`, leave the next node as it is.
+    Skip,
+}
+
+fn directive(node: &SyntaxNode) -> Option<Directive> {
+    if node.kind() != SyntaxKind::LineComment {
+        return None;
+    }
+    match node.text().strip_prefix("//")?.trim() {
+        "typstfmt: off" => Some(Directive::Off),
+        "typstfmt: on" => Some(Directive::On),
+        "typstfmt: skip" => Some(Directive::Skip),
+        _ => None,
+    }
+}
+
+/// Render the current child verbatim if a directive says not to format it.
+///
+/// Returns whether the child was rendered, in which case `children` has been moved on past all of
+/// the nodes that were covered.
+fn render_ignored(children: &mut Children, renderer: &mut Renderer) -> bool {
+    let Some(&child) = children.current() else {
+        return false;
+    };
+    let start = children.index - 1;
+
+    let end = if directive(child) == Some(Directive::Off) {
+        let on = children.items[start..]
+            .iter()
+            .position(|c| directive(c) == Some(Directive::On));
+        on.map_or(children.items.len() - 1, |on| start + on)
+    } else if !matches!(child.kind(), SyntaxKind::Space | SyntaxKind::Parbreak)
+        && (std::mem::take(&mut renderer.skip_first)
+            || children.items[..start]
+                .iter()
+                .rev()
+                .find(|c| !matches!(c.kind(), SyntaxKind::Space | SyntaxKind::Parbreak))
+                .is_some_and(|c| directive(c) == Some(Directive::Skip)))
+    {
+        if matches!(child.kind(), SyntaxKind::Code | SyntaxKind::Markup) {
+            // the directive is for the first node inside the sequence
+            renderer.skip_first = true;
+            return false;
+        } else if child.kind() == SyntaxKind::Hash && start + 1 < children.items.len() {
+            // keep the hash in markup together with its expression
+            start + 1
+        } else {
+            start
+        }
+    } else {
+        return false;
+    };
+
+    debug!("rendering ignored nodes");
+    render_verbatim(&children.items[start..=end], renderer);
+    children.index = end + 1;
+    true
+}
+
+/// Render the nodes exactly as they were written.
+fn render_verbatim(nodes: &[&SyntaxNode], renderer: &mut Renderer) {
+    let text: String = nodes
+        .iter()
+        .map(|c| (*c).clone().into_text().to_string())
+        .collect();
+    renderer.writer.push_verbatim(&text);
+    if nodes.last().is_some_and(|n| is_block(n)) {
+        renderer.writer.newline();
+    }
+}
+
 /// An AstNode that we can render.
 trait Renderable<'a>: AstNode<'a> + std::fmt::Debug + Clone {
     fn render_impl(&self, renderer: &mut Renderer) {
@@ -293,13 +408,13 @@ impl<'a> Children<'a> {
         }
     }
 
-    fn next(&mut self) -> Option<&&SyntaxNode> {
+    fn next(&mut self) -> Option<&&'a SyntaxNode> {
         let item = self.items.get(self.index);
         self.index += 1;
         item
     }
 
-    fn current(&self) -> Option<&&SyntaxNode> {
+    fn current(&self) -> Option<&&'a SyntaxNode> {
         if self.index > 0 {
             self.items.get(self.index - 1)
         } else {
@@ -307,25 +422,6 @@ impl<'a> Children<'a> {
         }
     }
 
-    fn has_next(&self, f: impl Fn(SyntaxKind) -> bool) -> bool {
-        let mut index = self.index;
-        loop {
-            let item = self.items.get(index);
-            index += 1;
-            if let Some(it) = item {
-                if f(it.kind()) {
-                    return true;
-                }
-            } else {
-                return false;
-            }
-        }
-    }
-
-    fn any(&self, f: impl FnMut(&&SyntaxNode) -> bool) -> bool {
-        self.items.iter().any(f)
-    }
-
     fn peek_next(&self) -> Option<&&SyntaxNode> {
         self.items.get(self.index)
     }
@@ -349,35 +445,271 @@ impl<'a> Children<'a> {
 
 impl<'a> Renderable<'a> for Markup<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        let mut children = Children::new(self.to_untyped());
-        while children.next().is_some() {
-            let child = children.current().unwrap();
-            if let Some(parbreak) = child.cast::<Parbreak>() {
-                if children.peek_prev().is_some() && children.peek_next().is_some() {
-                    parbreak.render(renderer);
-                } else {
-                    renderer.writer.newline();
+        let wrap = renderer.writer.config().markup_wrap;
+        render_markup(self.to_untyped(), renderer, wrap)
+    }
+}
+
+/// Render markup, rewrapping its paragraphs as configured when `wrap` is set.
+fn render_markup(node: &SyntaxNode, renderer: &mut Renderer, wrap: MarkupWrap) {
+    let wrap = if renderer.flat {
+        MarkupWrap::Preserve
+    } else {
+        wrap
+    };
+    // only the document's own nodes are kept whole, as errors nested in them make them erroneous
+    let keep_erroneous = std::mem::take(&mut renderer.keep_erroneous);
+    let mut children = Children::new(node);
+    // the number shown by the last item of the enum being rendered
+    let mut enum_number = None;
+    // the column that the descriptions of the term list being rendered are aligned to
+    let mut term_column = None;
+    while children.next().is_some() {
+        if render_ignored(&mut children, renderer) {
+            continue;
+        }
+        let child = children.current().unwrap();
+        if keep_erroneous && erroneous(child) {
+            debug!(?child, "keeping erroneous node");
+            render_verbatim(&[child], renderer);
+            enum_number = None;
+            term_column = None;
+            continue;
+        }
+        if wrap != MarkupWrap::Preserve {
+            let start = children.index - 1;
+            let (words, taken) = markup_words(&children.items[start..], renderer);
+            if taken > 0 {
+                render_words(&words, renderer, wrap);
+                children.index = start + taken;
+                continue;
+            }
+        }
+        let around_heading = [children.peek_prev(), children.peek_next()]
+            .into_iter()
+            .any(|n| n.is_some_and(|n| n.kind() == SyntaxKind::Heading));
+        let heading_spacing = renderer
+            .writer
+            .config()
+            .blank_lines_around_headings
+            .filter(|_| !renderer.flat);
+        if child.kind() != SyntaxKind::EnumItem && !child.kind().is_trivia() {
+            enum_number = None;
+        }
+        if child.kind() != SyntaxKind::TermItem && !child.kind().is_trivia() {
+            term_column = None;
+        }
+        if let Some(item) = child.cast::<EnumItem>() {
+            let style = renderer.writer.config().enum_marker;
+            let (marker, number) = enum_marker(style, item, enum_number);
+            render_list_item(child, renderer, marker.as_deref());
+            enum_number = number;
+        } else if child.kind() == SyntaxKind::TermItem
+            && renderer.writer.config().align_term_descriptions
+            && !renderer.flat
+        {
+            let column = *term_column.get_or_insert_with(|| {
+                term_description_column(&children.items[children.index - 1..], renderer)
+            });
+            render_term_item(child, renderer, Some(column));
+        } else if let Some(blank_lines) = heading_spacing.filter(|_| {
+            matches!(child.kind(), SyntaxKind::Space | SyntaxKind::Parbreak)
+                && around_heading
+                && children.peek_prev().is_some()
+                && children.peek_next().is_some()
+        }) {
+            renderer.writer.exact_blank_lines(blank_lines);
+        } else if let Some(parbreak) = child.cast::<Parbreak>() {
+            if children.peek_prev().is_some() && children.peek_next().is_some() {
+                parbreak.render(renderer);
+            } else {
+                renderer.writer.newline();
+            }
+        } else if let Some(expr) = child.cast::<Expr>() {
+            expr.render(renderer);
+        } else if children
+            .peek_prev()
+            .map_or(children.peek_next().is_none(), |n| is_block(n))
+            && child.kind() == SyntaxKind::Space
+        {
+            // skip adding newlines or spacing after block elements as they handle newlines
+            // themselves
+        } else {
+            render_anon(child, renderer);
+        }
+    }
+    renderer.keep_erroneous = keep_erroneous;
+}
+
+/// A word of a paragraph, which is never broken over lines.
+#[derive(Debug)]
+struct Word {
+    text: String,
+    /// Whether the line has to end after the word, such as after a linebreak.
+    break_after: bool,
+}
+
+/// Split the start of a paragraph into words, as far as it is made of text and things that fit on
+/// a line.
+///
+/// Returns the words and the number of nodes they were taken from, which is zero when the first
+/// node can't be part of a paragraph.
+fn markup_words(nodes: &[&SyntaxNode], renderer: &Renderer) -> (Vec<Word>, usize) {
+    let mut words: Vec<Word> = Vec::new();
+    // whether the next piece of text continues the last word
+    let mut joined = false;
+    let mut taken = 0;
+    let mut after_code = false;
+    // spaces before a paragraph are left to the markup around it
+    if renderer.skip_first || nodes.first().is_none_or(|n| n.kind() == SyntaxKind::Space) {
+        return (words, taken);
+    }
+    fn add(words: &mut Vec<Word>, joined: &mut bool, piece: &str) {
+        match words.last_mut() {
+            Some(word) if *joined => word.text.push_str(piece),
+            _ => words.push(Word {
+                text: piece.to_owned(),
+                break_after: false,
+            }),
+        }
+        *joined = true;
+    }
+
+    for (i, node) in nodes.iter().enumerate() {
+        match node.kind() {
+            SyntaxKind::Space => {
+                // lines of embedded code are kept apart, rather than joined into a paragraph
+                let next_is_code = nodes
+                    .get(i + 1)
+                    .is_some_and(|n| n.kind() == SyntaxKind::Hash);
+                if node.text().contains('\n') && (after_code || next_is_code) {
+                    if let Some(word) = words.last_mut() {
+                        word.break_after = true;
+                    }
                 }
-            } else if let Some(expr) = child.cast::<Expr>() {
-                expr.render(renderer);
-            } else if children
-                .peek_prev()
-                .map_or(children.peek_next().is_none(), |n| is_block(n))
-                && child.kind() == SyntaxKind::Space
-            {
-                // skip adding newlines or spacing after block elements as they handle newlines
-                // themselves
+                joined = false;
+                continue;
+            }
+            SyntaxKind::Text => {
+                let text = node.text();
+                if text.starts_with(char::is_whitespace) {
+                    joined = false;
+                }
+                for (j, piece) in text.split_whitespace().enumerate() {
+                    if j > 0 {
+                        joined = false;
+                    }
+                    add(&mut words, &mut joined, piece);
+                }
+                if text.ends_with(char::is_whitespace) {
+                    joined = false;
+                }
+                after_code = false;
+            }
+            SyntaxKind::Linebreak => {
+                add(&mut words, &mut joined, node.text());
+                if let Some(word) = words.last_mut() {
+                    word.break_after = true;
+                }
+                joined = false;
+                after_code = false;
+            }
+            _ => {
+                if !is_inline(node, nodes.get(i + 1).copied()) || erroneous(node) {
+                    break;
+                }
+                let Some(text) =
+                    flat_text(renderer, |trial| render_typed_or_text::<Expr>(node, trial))
+                else {
+                    break;
+                };
+                add(&mut words, &mut joined, &text);
+                after_code = i > 0 && nodes[i - 1].kind() == SyntaxKind::Hash;
+            }
+        }
+        taken = i + 1;
+    }
+    (words, taken)
+}
+
+/// Whether the node can be part of a paragraph that gets rewrapped, given the node after it.
+fn is_inline(node: &SyntaxNode, next: Option<&SyntaxNode>) -> bool {
+    match node.kind() {
+        SyntaxKind::Parbreak
+        | SyntaxKind::Heading
+        | SyntaxKind::LineComment
+        | SyntaxKind::BlockComment => false,
+        SyntaxKind::Hash => !next.is_some_and(is_statement),
+        _ => !is_block(node) && !is_statement(node),
+    }
+}
+
+/// Whether the node is a statement, which runs to the end of its line when embedded in markup.
+fn is_statement(node: &SyntaxNode) -> bool {
+    matches!(
+        node.kind(),
+        SyntaxKind::LetBinding
+            | SyntaxKind::SetRule
+            | SyntaxKind::ShowRule
+            | SyntaxKind::ModuleImport
+            | SyntaxKind::ModuleInclude
+            | SyntaxKind::Conditional
+            | SyntaxKind::WhileLoop
+            | SyntaxKind::ForLoop
+            | SyntaxKind::Contextual
+            | SyntaxKind::LoopBreak
+            | SyntaxKind::LoopContinue
+            | SyntaxKind::FuncReturn
+    )
+}
+
+/// Write the words of a paragraph, either filling lines up to the maximum width or starting a new
+/// line for each sentence.
+fn render_words(words: &[Word], renderer: &mut Renderer, wrap: MarkupWrap) {
+    let max_width = renderer.writer.config().max_width;
+    for (i, word) in words.iter().enumerate() {
+        if let Some(previous) = i.checked_sub(1).map(|i| &words[i]) {
+            let break_line = match wrap {
+                MarkupWrap::Preserve => false,
+                MarkupWrap::Fill => {
+                    renderer.writer.current_width() + 1 + word.text.chars().count() > max_width
+                }
+                MarkupWrap::Sentence => {
+                    ends_sentence(&previous.text) && !word.text.starts_with(char::is_lowercase)
+                }
+            };
+            if (previous.break_after || break_line) && can_start_line(&word.text) {
+                renderer.writer.newline();
             } else {
-                render_anon(child, renderer);
+                renderer.writer.space();
             }
         }
+        renderer.writer.push(&word.text);
     }
 }
 
+/// Whether the word ends a sentence, even with closing quotes or brackets after the full stop.
+fn ends_sentence(word: &str) -> bool {
+    word.trim_end_matches(['"', '\'', ')', ']', '*', '_', '”', '’'])
+        .ends_with(['.', '!', '?'])
+}
+
+/// Whether the word can go at the start of a line without becoming the marker of a list, enum,
+/// term or heading.
+fn can_start_line(word: &str) -> bool {
+    let is_enum_marker = word
+        .strip_suffix('.')
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()));
+    !(matches!(word, "-" | "+" | "/") || word.chars().all(|c| c == '=') || is_enum_marker)
+}
+
 impl<'a> Renderable<'a> for CodeBlock<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
         let mut children = Children::new(self.to_untyped());
         while children.next().is_some() {
+            if render_ignored(&mut children, renderer) {
+                continue;
+            }
             let child = children.current().unwrap();
             if let Some(code) = child.cast::<Code>() {
                 code.render(renderer);
@@ -397,7 +729,20 @@ impl<'a> Renderable<'a> for CodeBlock<'a> {
 
 impl<'a> Renderable<'a> for Code<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        render_children_typed_or_text::<Expr>(self, renderer)
+        let mut children = Children::new(self.to_untyped());
+        while let Some(child) = children.next().copied() {
+            if render_ignored(&mut children, renderer) {
+                continue;
+            }
+            if child.kind() == SyntaxKind::Space
+                && children.peek_prev().is_some_and(|n| is_block(n))
+            {
+                // block elements end their own line so only keep blank lines after them
+                renderer.writer.blank_lines(blank_lines(child));
+            } else {
+                render_typed_or_text::<Expr>(child, renderer);
+            }
+        }
     }
 }
 
@@ -405,8 +750,10 @@ impl<'a> Renderable<'a> for Text<'a> {}
 impl<'a> Renderable<'a> for Space<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
         let text = self.to_untyped().text();
-        if text.contains("\n\n") {
-            renderer.writer.parbreak();
+        // counting newlines works for any line ending
+        let blank = blank_lines(self.to_untyped());
+        if blank > 0 {
+            renderer.writer.blank_lines(blank);
         } else if text.contains('\n') {
             // convert newlines to newlines with indent
             renderer.writer.newline();
@@ -418,9 +765,14 @@ impl<'a> Renderable<'a> for Space<'a> {
 impl<'a> Renderable<'a> for Linebreak<'a> {}
 impl<'a> Renderable<'a> for Parbreak<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        renderer.writer.parbreak();
+        renderer.writer.blank_lines(blank_lines(self.to_untyped()));
     }
 }
+
+/// The number of blank lines in some whitespace.
+fn blank_lines(space: &SyntaxNode) -> usize {
+    space.text().matches('\n').count().saturating_sub(1)
+}
 impl<'a> Renderable<'a> for Escape<'a> {}
 impl<'a> Renderable<'a> for Shorthand<'a> {}
 impl<'a> Renderable<'a> for SmartQuote<'a> {}
@@ -444,73 +796,198 @@ impl<'a> Renderable<'a> for Ref<'a> {
 }
 impl<'a> Renderable<'a> for Heading<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        render_children_typed_or_text::<Markup>(self, renderer)
+        // a heading has to stay on its line
+        for child in self.to_untyped().children() {
+            match child.kind() {
+                SyntaxKind::Space => {
+                    renderer.writer.push(" ");
+                }
+                SyntaxKind::Markup => render_markup(child, renderer, MarkupWrap::Preserve),
+                _ => render_anon(child, renderer),
+            }
+        }
     }
 }
 impl<'a> Renderable<'a> for ListItem<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        let mut children = Children::new(self.to_untyped());
-        while let Some(child) = children.next() {
-            if child.kind() == SyntaxKind::ListMarker {
-                render_anon(child, renderer);
-                renderer.writer.space();
-                renderer.writer.inc_indent();
-            } else if child.kind() == SyntaxKind::Space {
-                // skip
-            } else {
-                render_anon(child, renderer);
-            }
-        }
-        renderer.writer.newline().dec_indent(true);
+        render_list_item(self.to_untyped(), renderer, None)
     }
 }
 impl<'a> Renderable<'a> for EnumItem<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        let mut children = Children::new(self.to_untyped());
-        while let Some(child) = children.next() {
-            if child.kind() == SyntaxKind::EnumMarker {
-                render_anon(child, renderer);
-                renderer.writer.space();
-                renderer.writer.inc_indent();
-            } else if child.kind() == SyntaxKind::Space {
-                // skip
-            } else {
-                render_anon(child, renderer);
-            }
-        }
-        renderer.writer.newline().dec_indent(true);
+        render_list_item(self.to_untyped(), renderer, None)
     }
 }
 impl<'a> Renderable<'a> for TermItem<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        let mut children = Children::new(self.to_untyped());
-        while let Some(child) = children.next() {
-            if child.kind() == SyntaxKind::TermMarker {
+        render_term_item(self.to_untyped(), renderer, None)
+    }
+}
+
+/// Render a term item, optionally with its description aligned to a column past the start of the
+/// item and filled with a hanging indent under it.
+fn render_term_item(node: &SyntaxNode, renderer: &mut Renderer, column: Option<usize>) {
+    let start = renderer.writer.current_width();
+    // the term has to stay on the line of its marker
+    let mut in_term = true;
+    // the columns the description is indented by past the indentation of the item
+    let mut hanging = 0;
+    for child in node.children() {
+        match child.kind() {
+            SyntaxKind::TermMarker => {
                 render_anon(child, renderer);
                 renderer.writer.space();
                 renderer.writer.inc_indent();
-            } else if child.kind() == SyntaxKind::Colon {
+            }
+            SyntaxKind::Colon => {
                 renderer.writer.push(":");
+                in_term = false;
+            }
+            SyntaxKind::Space => {}
+            SyntaxKind::Markup if in_term => render_markup(child, renderer, MarkupWrap::Preserve),
+            SyntaxKind::Markup => {
+                let mut wrap = renderer.writer.config().markup_wrap;
+                let on_next_line = child.children().next().is_none_or(|first| {
+                    first.kind() == SyntaxKind::Space && first.text().contains('\n')
+                });
+                match column {
+                    _ if on_next_line => {}
+                    Some(column) => {
+                        let padding =
+                            (start + column).saturating_sub(renderer.writer.current_width());
+                        renderer.writer.push(&" ".repeat(padding.max(1)));
+                        hanging = column.saturating_sub(renderer.writer.config().indent_width());
+                        renderer.writer.indent_by(hanging);
+                        if wrap == MarkupWrap::Preserve {
+                            wrap = MarkupWrap::Fill;
+                        }
+                    }
+                    _ => {
+                        renderer.writer.space();
+                    }
+                }
+                render_markup(child, renderer, wrap);
+            }
+            _ => render_anon(child, renderer),
+        }
+    }
+    if !renderer.writer.at_line_start() {
+        renderer.writer.newline();
+    }
+    renderer.writer.dedent_by(hanging).dec_indent(true);
+}
+
+/// The column past the start of a term item that the descriptions of the term list starting at
+/// the first node are aligned to, one more than the widest term that fits on a line.
+fn term_description_column(nodes: &[&SyntaxNode], renderer: &Renderer) -> usize {
+    nodes
+        .iter()
+        .take_while(|node| node.kind() == SyntaxKind::TermItem || node.kind().is_trivia())
+        .filter_map(|node| node.cast::<TermItem>())
+        .filter_map(|item| {
+            flat_text(renderer, |trial| {
+                trial.writer.push("/ ");
+                render_markup(item.term().to_untyped(), trial, MarkupWrap::Preserve);
+                trial.writer.push(":");
+            })
+        })
+        .map(|text| text.chars().count() + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Render a list or enum item with its content indented by a level, optionally replacing its
+/// marker.
+fn render_list_item(node: &SyntaxNode, renderer: &mut Renderer, marker: Option<&str>) {
+    for child in node.children() {
+        match child.kind() {
+            SyntaxKind::ListMarker | SyntaxKind::EnumMarker => {
+                renderer.writer.push(marker.unwrap_or(child.text()));
                 renderer.writer.space();
-            } else if child.kind() == SyntaxKind::Space {
-                // skip
-            } else {
-                render_anon(child, renderer);
+                renderer.writer.inc_indent();
+            }
+            SyntaxKind::Space => {}
+            SyntaxKind::Markup => {
+                let wrap = renderer.writer.config().markup_wrap;
+                render_markup(child, renderer, wrap)
             }
+            _ => render_anon(child, renderer),
         }
-        renderer.writer.newline().dec_indent(true);
     }
+    // a nested item at the end has already ended the line
+    if !renderer.writer.at_line_start() {
+        renderer.writer.newline();
+    }
+    renderer.writer.dec_indent(true);
+}
+
+/// The marker to write for an enum item as configured, and the number the item shows with it.
+///
+/// `previous` is the number shown by the item before it in the enum, or none when it starts an
+/// enum.
+/// Items only separated by paragraph breaks are still one enum, whose numbering carries on.
+fn enum_marker(
+    style: EnumMarker,
+    item: EnumItem,
+    previous: Option<usize>,
+) -> (Option<String>, Option<usize>) {
+    let auto = previous.map_or(1, |previous| previous + 1);
+    let number = match (style, previous) {
+        (EnumMarker::Preserve, _) => return (None, None),
+        (EnumMarker::Plus, _) | (EnumMarker::KeepFirst, None) => item.number().unwrap_or(auto),
+        (EnumMarker::Sequential, _) | (EnumMarker::KeepFirst, Some(_)) => auto,
+    };
+    let marker = match style {
+        // any number other than the one the item would get anyway has to be written
+        EnumMarker::Plus if number == auto => "+".to_owned(),
+        _ => format!("{number}."),
+    };
+    (Some(marker), Some(number))
 }
 
 impl<'a> Renderable<'a> for Equation<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        render_children_typed_or_text::<Math>(self, renderer)
+        let children: Vec<_> = self.to_untyped().children().collect();
+        let Some(math) = children.iter().find(|c| c.kind() == SyntaxKind::Math) else {
+            return render_children_typed_or_text::<Math>(self, renderer);
+        };
+        // spaces just inside both dollars make a display equation, which has to be kept
+        let leading = children
+            .get(1)
+            .is_some_and(|c| c.kind() == SyntaxKind::Space);
+        let trailing =
+            children.len() > 3 && children[children.len() - 2].kind() == SyntaxKind::Space;
+        // decided by the math alone, so that arguments broken over several lines don't make it
+        // multiline the next time around
+        let multiline = leading
+            && trailing
+            && math
+                .children()
+                .any(|c| matches!(c.kind(), SyntaxKind::Linebreak | SyntaxKind::LineComment));
+
+        if multiline {
+            renderer.writer.open_grouping("$").newline();
+        } else {
+            renderer.writer.push("$");
+            if leading {
+                renderer.writer.push(" ");
+            }
+        }
+        render_math(math, renderer, multiline);
+        if multiline {
+            renderer.writer.newline().close_grouping("$");
+        } else {
+            if trailing {
+                renderer.writer.space();
+            }
+            renderer.writer.push("$");
+        }
     }
 }
 
 impl<'a> Renderable<'a> for Math<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        render_children_typed_or_text::<Expr>(self, renderer)
+        render_math(self.to_untyped(), renderer, false)
     }
 }
 
@@ -523,7 +1000,12 @@ impl<'a> Renderable<'a> for MathDelimited<'a> {
 }
 impl<'a> Renderable<'a> for MathAttach<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        render_children_typed_or_text::<Expr>(self, renderer)
+        // attachments go right against what they are attached to
+        for child in self.to_untyped().children() {
+            if child.kind() != SyntaxKind::Space {
+                render_typed_or_text::<Expr>(child, renderer);
+            }
+        }
     }
 }
 impl<'a> Renderable<'a> for MathFrac<'a> {
@@ -577,46 +1059,36 @@ impl<'a> Renderable<'a> for Parenthesized<'a> {
 }
 impl<'a> Renderable<'a> for Array<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        // TODO: can't use ArrayItem instead of Expr here because the spread variant doesn't
-        // include the dots.
-        render_children_typed_or_text::<Expr>(self, renderer)
+        let children: Vec<_> = self.to_untyped().children().collect();
+        if children.first().map(|c| c.kind()) == Some(SyntaxKind::LeftParen) {
+            render_group(&children, renderer, Group::Array);
+            return;
+        }
+        // a row of a matrix in math, e.g. `1, 2` in `mat(1, 2; 3, 4)`, whose commas are kept as
+        // written
+        let mut after_comma = false;
+        for child in children {
+            match child.kind() {
+                SyntaxKind::Space => {}
+                SyntaxKind::Comma => {
+                    renderer.writer.push(",");
+                    after_comma = true;
+                }
+                _ => {
+                    if after_comma {
+                        renderer.writer.push(" ");
+                    }
+                    render_group_item(child, renderer);
+                    after_comma = false;
+                }
+            }
+        }
     }
 }
 impl<'a> Renderable<'a> for Dict<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        let mut children = Children::new(self.to_untyped());
-        let multiline = is_multiline(&children);
-        let past_argument = |children: &Children, renderer: &mut Renderer| {
-            if multiline {
-                renderer.writer.push(",").newline();
-            } else if children.has_next(|k| {
-                !k.is_trivia()
-                    && !k.is_grouping()
-                    && k != SyntaxKind::ContentBlock
-                    && k != SyntaxKind::Comma
-            }) {
-                renderer.writer.push(",");
-                renderer.writer.push(" ");
-            }
-        };
-        while let Some(child) = children.next() {
-            if let Some(expr) = child.cast::<Keyed>() {
-                expr.render(renderer);
-                past_argument(&children, renderer);
-            } else if let Some(named) = child.cast::<Named>() {
-                named.render(renderer);
-                past_argument(&children, renderer);
-            } else if let Some(spread) = child.cast::<Spread>() {
-                spread.render(renderer);
-                past_argument(&children, renderer);
-            } else if multiline && child.kind() == SyntaxKind::LeftParen {
-                renderer.writer.open_grouping(child.text()).newline();
-            } else if child.kind() == SyntaxKind::Comma || child.kind() == SyntaxKind::Space {
-                // skip
-            } else {
-                render_anon(child, renderer);
-            }
-        }
+        let children: Vec<_> = self.to_untyped().children().collect();
+        render_group(&children, renderer, Group::Dict);
     }
 }
 impl<'a> Renderable<'a> for Unary<'a> {
@@ -648,16 +1120,38 @@ impl<'a> Renderable<'a> for Binary<'a> {
 }
 impl<'a> Renderable<'a> for FieldAccess<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
-        render_children_typed_or_text_2::<Expr, Ident>(self, renderer)
+        // the field follows the target on its line
+        let field = 1 + self.field().as_str().chars().count();
+        with_trailing(renderer, field + renderer.trailing, |renderer| {
+            self.target().render(renderer)
+        });
+        renderer.writer.push(".");
+        self.field().render(renderer);
     }
 }
 impl<'a> Renderable<'a> for FuncCall<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
         for child in self.to_untyped().children() {
             if let Some(typed) = child.cast::<Expr>() {
-                typed.render(renderer);
+                // the arguments follow the callee on its line, which matters for method calls
+                let args = match typed {
+                    Expr::FieldAccess(_) if !renderer.flat => {
+                        first_line_width(&[self.args().to_untyped()], renderer)
+                    }
+                    _ => 0,
+                };
+                with_trailing(renderer, args + renderer.trailing, |renderer| {
+                    typed.render(renderer)
+                });
             } else if child.kind() == SyntaxKind::Args {
-                render_args(child, renderer)
+                if !render_table_args(self.callee(), child, renderer) {
+                    let group = if is_math_callee(self.callee()) {
+                        Group::MathArgs
+                    } else {
+                        Group::Args
+                    };
+                    render_args(child, renderer, group)
+                }
             } else {
                 render_anon(child, renderer);
             }
@@ -726,7 +1220,7 @@ impl<'a> Renderable<'a> for SetRule<'a> {
     fn render_impl(&self, renderer: &mut Renderer) {
         for child in self.to_untyped().children() {
             if child.kind() == SyntaxKind::Args {
-                render_args(child, renderer)
+                render_args(child, renderer, Group::Args)
             } else if let Some(typed) = child.cast::<Expr>() {
                 typed.render(renderer);
             } else {
@@ -968,75 +1462,557 @@ impl<'a> Renderable<'a> for Expr<'a> {
     }
 }
 
-// TODO: can't use ArrayItem instead of Expr here because the spread variant doesn't
-// include the dots.
-fn render_args(node: &SyntaxNode, renderer: &mut Renderer) {
-    let mut children = Children::new(node);
-    let multiline = is_multiline(&children);
-    debug!(?node, ?multiline, "render_args");
-    let mut in_parens = false;
-    let past_argument = |children: &Children, renderer: &mut Renderer, in_parens: bool| {
-        if in_parens {
-            if multiline {
-                renderer.writer.push(",").newline();
-            } else if children.has_next(|k| {
-                !k.is_trivia()
-                    && !k.is_grouping()
-                    && k != SyntaxKind::ContentBlock
-                    && k != SyntaxKind::Colon
-            }) {
-                renderer.writer.push(",");
-                renderer.writer.push(" ");
+/// Operators and relations that get a space either side of them in math.
+const MATH_OPERATORS: &[&str] = &[
+    "+", "-", "*", "=", "<", ">", "<=", ">=", "!=", "==", ":=", "::=", "=:", "<<", ">>", "<<<",
+    ">>>", "->", "-->", "<-", "<--", "<->", "<-->", "=>", "==>", "<==", "<=>", "<==>", "|->", "~",
+    "~~", "±", "∓", "×", "·", "÷", "≤", "≥", "≠", "≈", "≡", "∈", "∉", "→", "←", "⇒", "⇔",
+];
+
+/// Operators that can also be the sign of what follows them.
+const MATH_SIGNS: &[&str] = &["+", "-", "±", "∓"];
+
+/// The role of an item of math, for working out the space around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MathItem {
+    Operator,
+    /// An operator used as a sign, e.g. the `-` of `-x`.
+    Sign,
+    AlignPoint,
+    Linebreak,
+    LineComment,
+    /// Something after which an operator is a sign, such as an opening parenthesis or a comma.
+    Opening,
+    Other,
+}
+
+/// The whitespace between two items of math.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MathGap {
+    None,
+    Space,
+    Newline,
+}
+
+fn math_item(node: &SyntaxNode, previous: Option<MathItem>) -> MathItem {
+    match node.kind() {
+        SyntaxKind::MathAlignPoint => MathItem::AlignPoint,
+        SyntaxKind::Linebreak => MathItem::Linebreak,
+        SyntaxKind::LineComment => MathItem::LineComment,
+        SyntaxKind::LeftParen => MathItem::Opening,
+        SyntaxKind::Text | SyntaxKind::Shorthand => {
+            let text = node.text().as_str();
+            let after_operand = matches!(previous, Some(MathItem::Other));
+            if MATH_SIGNS.contains(&text) && !after_operand {
+                MathItem::Sign
+            } else if MATH_OPERATORS.contains(&text) {
+                MathItem::Operator
+            } else if matches!(text, "(" | "[" | "{" | "," | ";") {
+                MathItem::Opening
+            } else {
+                MathItem::Other
             }
         }
-    };
-    while let Some(child) = children.next() {
-        if let Some(expr) = child.cast::<Expr>() {
-            expr.render(renderer);
-            past_argument(&children, renderer, in_parens);
-        } else if let Some(named) = child.cast::<Named>() {
-            named.render(renderer);
-            past_argument(&children, renderer, in_parens);
-        } else if let Some(spread) = child.cast::<Spread>() {
-            spread.render(renderer);
-            past_argument(&children, renderer, in_parens);
-        } else if child.kind() == SyntaxKind::LeftParen {
-            in_parens = true;
-            if multiline {
-                renderer.writer.open_grouping(child.text()).newline();
+        _ => MathItem::Other,
+    }
+}
+
+/// The whitespace to put between two items of math, given what was there before.
+fn math_gap(previous: MathItem, next: MathItem, gap: MathGap, break_lines: bool) -> MathGap {
+    use MathItem::*;
+    match (previous, next) {
+        // the comment has already ended its line
+        (LineComment, _) => MathGap::None,
+        // a linebreak is only one when followed by whitespace
+        (Linebreak, _) if break_lines => MathGap::Newline,
+        (Linebreak, _) | (_, Linebreak) => gap.max(MathGap::Space),
+        (AlignPoint, Operator) => MathGap::None,
+        // operators next to each other could otherwise become a different one
+        (Sign, Operator | Sign) => gap,
+        (Sign, _) => MathGap::None,
+        (Operator | AlignPoint, _) | (_, Operator | AlignPoint) => gap.max(MathGap::Space),
+        _ => gap,
+    }
+}
+
+/// Render the items of math with normalised spacing: a space either side of operators and
+/// alignment points, none after a sign, and otherwise whitespace kept where there was some.
+///
+/// When `break_lines` is set, every linebreak is followed by a newline.
+fn render_math(math: &SyntaxNode, renderer: &mut Renderer, break_lines: bool) {
+    let mut previous = None;
+    // comments don't change whether an operator is a sign
+    let mut previous_code = None;
+    let mut gap = MathGap::None;
+    for child in math.children() {
+        if child.kind() == SyntaxKind::Space {
+            let space = if child.text().contains('\n') {
+                MathGap::Newline
             } else {
-                render_anon(child, renderer);
+                MathGap::Space
+            };
+            gap = gap.max(space);
+            continue;
+        }
+        let item = math_item(child, previous_code);
+        if let Some(previous) = previous {
+            match math_gap(previous, item, gap, break_lines) {
+                MathGap::None => {}
+                MathGap::Space => {
+                    renderer.writer.space();
+                }
+                MathGap::Newline => {
+                    renderer.writer.newline();
+                }
             }
-        } else if child.kind() == SyntaxKind::RightParen {
-            in_parens = false;
-            render_anon(child, renderer);
-        } else if child.kind() == SyntaxKind::Comma || child.kind() == SyntaxKind::Space {
-            // skip
-        } else {
-            render_anon(child, renderer);
         }
+        render_typed_or_text::<Expr>(child, renderer);
+        previous = Some(item);
+        if !child.kind().is_trivia() {
+            previous_code = Some(item);
+        }
+        gap = MathGap::None;
     }
 }
 
-fn render_params(node: &SyntaxNode, renderer: &mut Renderer) {
-    debug!(?node, "render_params");
-    let mut children = Children::new(node);
-    let multiline = is_multiline(&children);
-    while let Some(child) = children.next() {
-        if let Some(param) = child.cast::<Param>() {
-            param.render(renderer);
-            if multiline {
-                renderer.writer.push(",").newline();
-            } else if children.has_next(|k| !k.is_trivia() && !k.is_grouping()) {
+/// What a parenthesised group of items is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Group {
+    /// The arguments of a function call, whose single block-like item can be hugged.
+    Args,
+    /// The arguments of a function call in math, which can be hugged like [`Group::Args`] but
+    /// whose trailing comma is kept as written, as it can add an empty argument.
+    MathArgs,
+    /// The parameters of a closure.
+    Params,
+    /// An array, whose single item has to keep a comma after it.
+    Array,
+    /// A dictionary.
+    Dict,
+}
+
+/// How a parenthesised group of items gets laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// All items on a single line, separated by `, `.
+    Flat,
+    /// A single item kept against the parentheses, leaving it to break itself.
+    Hug,
+    /// One item per line, separated by commas.
+    Broken,
+}
+
+/// Whether the node is an item of a parenthesised group, rather than punctuation or trivia.
+fn is_group_item(node: &SyntaxNode) -> bool {
+    !node.kind().is_trivia()
+        && !matches!(
+            node.kind(),
+            SyntaxKind::LeftParen
+                | SyntaxKind::RightParen
+                | SyntaxKind::Comma
+                | SyntaxKind::Semicolon
+                | SyntaxKind::Colon
+        )
+}
+
+/// Whether a group consisting of just this item can keep it against the parentheses.
+fn is_huggable(node: &SyntaxNode) -> bool {
+    matches!(
+        node.kind(),
+        SyntaxKind::ContentBlock
+            | SyntaxKind::CodeBlock
+            | SyntaxKind::FuncCall
+            | SyntaxKind::Closure
+            | SyntaxKind::Array
+            | SyntaxKind::Dict
+    )
+}
+
+fn render_group_item(node: &SyntaxNode, renderer: &mut Renderer) {
+    if let Some(named) = node.cast::<Named>() {
+        named.render(renderer);
+    } else if let Some(keyed) = node.cast::<Keyed>() {
+        keyed.render(renderer);
+    } else if let Some(spread) = node.cast::<Spread>() {
+        spread.render(renderer);
+    } else if let Some(pattern) = node.cast::<Pattern>() {
+        pattern.render(renderer);
+    } else if let Some(math) = node.cast::<Math>() {
+        math.render(renderer);
+    } else {
+        render_anon(node, renderer);
+    }
+}
+
+/// Render a comma separated group of items, from the opening to the closing parenthesis.
+///
+/// The group is kept on a single line if it fits within the configured `max_width`, otherwise
+/// each item is put on its own line.
+/// Arguments of a single block-like item instead stay against the parentheses and that item may
+/// break itself.
+/// Whether the last item is followed by a comma is up to the configured `trailing_comma`.
+fn render_group(nodes: &[&SyntaxNode], renderer: &mut Renderer, group: Group) {
+    let layout = group_layout(nodes, renderer, group);
+    debug!(?layout, "render_group");
+    render_group_with(nodes, renderer, group, layout);
+}
+
+fn group_layout(nodes: &[&SyntaxNode], renderer: &Renderer, group: Group) -> Layout {
+    if renderer.flat {
+        return Layout::Flat;
+    }
+
+    let mut trial = Renderer {
+        writer: Writer::new(renderer.writer.config().clone()),
+        flat: true,
+        skip_first: false,
+        keep_erroneous: false,
+        trailing: 0,
+    };
+    render_group_with(nodes, &mut trial, group, Layout::Flat);
+    if let Some(width) = trial.writer.flat_width() {
+        let line = renderer.writer.current_width() + width + renderer.trailing;
+        if line <= renderer.writer.config().max_width {
+            return Layout::Flat;
+        }
+    }
+
+    // comments count as items so that they never get hugged
+    let mut items = nodes.iter().filter(|n| {
+        !matches!(
+            n.kind(),
+            SyntaxKind::LeftParen
+                | SyntaxKind::RightParen
+                | SyntaxKind::Comma
+                | SyntaxKind::Semicolon
+                | SyntaxKind::Space
+        )
+    });
+    match (items.next(), items.next()) {
+        (Some(item), None)
+            if matches!(group, Group::Args | Group::MathArgs) && is_huggable(item) =>
+        {
+            Layout::Hug
+        }
+        _ => Layout::Broken,
+    }
+}
+
+fn render_group_with(nodes: &[&SyntaxNode], renderer: &mut Renderer, group: Group, layout: Layout) {
+    let total = nodes.iter().filter(|n| is_group_item(n)).count();
+    let trailing_comma = match group {
+        Group::Array if total == 1 => true,
+        Group::MathArgs => nodes
+            .iter()
+            .rev()
+            .find(|n| !n.kind().is_trivia() && n.kind() != SyntaxKind::RightParen)
+            .is_some_and(|n| n.kind() == SyntaxKind::Comma),
+        _ => match (renderer.writer.config().trailing_comma, layout) {
+            (_, Layout::Hug) | (TrailingComma::Never, _) => false,
+            (TrailingComma::Always, _) => true,
+            (TrailingComma::Multiline, layout) => layout == Layout::Broken,
+        },
+    };
+    let mut seen = 0;
+    // when broken, the newline after an item is held back so that a comment on the same line can
+    // stay after it
+    let mut pending_newline = false;
+    let mut line_ended = false;
+    let mut after_comment = false;
+    let mut spaced = false;
+    for (i, node) in nodes.iter().enumerate() {
+        if node.kind() == SyntaxKind::LeftParen {
+            renderer.writer.open_grouping(node.text());
+            if layout == Layout::Broken {
+                renderer.writer.newline();
+            }
+        } else if node.kind() == SyntaxKind::RightParen {
+            if pending_newline {
+                renderer.writer.newline();
+            }
+            renderer.writer.close_grouping(node.text());
+        } else if node.kind() == SyntaxKind::Space {
+            line_ended |= node.text().contains('\n');
+            spaced = true;
+        } else if matches!(node.kind(), SyntaxKind::Comma | SyntaxKind::Semicolon) {
+            // skip
+        } else if is_group_item(node) {
+            if pending_newline {
+                renderer.writer.newline();
+            } else if after_comment && spaced {
+                renderer.writer.space();
+            }
+            // a hugged item is followed by the closing parenthesis, others by a comma at most
+            let trailing = match layout {
+                Layout::Hug => 1 + renderer.trailing,
+                _ => 1,
+            };
+            with_trailing(renderer, trailing, |renderer| {
+                render_group_item(node, renderer)
+            });
+            seen += 1;
+            // rows of arguments in math are ended by semicolons, which are always kept
+            let row_end = nodes[i + 1..]
+                .iter()
+                .find(|n| !n.kind().is_trivia())
+                .is_some_and(|n| n.kind() == SyntaxKind::Semicolon);
+            if row_end {
+                renderer.writer.push(";");
+            } else if seen < total || trailing_comma {
                 renderer.writer.push(",");
+            }
+            if layout == Layout::Broken {
+                pending_newline = true;
+                line_ended = false;
+            } else if seen < total {
                 renderer.writer.push(" ");
             }
-        } else if multiline && child.kind() == SyntaxKind::LeftParen {
-            renderer.writer.open_grouping(child.text()).newline();
-        } else if child.kind() == SyntaxKind::Comma || child.kind() == SyntaxKind::Space {
-            // skip
+            after_comment = false;
+        } else if node.kind().is_trivia() {
+            if pending_newline && line_ended {
+                renderer.writer.newline();
+            } else if pending_newline || (seen == total && seen > 0) {
+                renderer.writer.space();
+            }
+            render_anon(node, renderer);
+            // line comments and block comments over several lines end their line themselves
+            pending_newline = layout == Layout::Broken
+                && node.kind() == SyntaxKind::BlockComment
+                && !node.text().contains('\n');
+            line_ended = false;
+            after_comment = true;
+            spaced = false;
         } else {
-            render_anon(child, renderer);
+            render_anon(node, renderer);
+        }
+    }
+}
+
+fn render_args(node: &SyntaxNode, renderer: &mut Renderer, group: Group) {
+    debug!(?node, "render_args");
+    let children: Vec<_> = node.children().collect();
+    let open = children
+        .iter()
+        .position(|c| c.kind() == SyntaxKind::LeftParen);
+    let close = children
+        .iter()
+        .position(|c| c.kind() == SyntaxKind::RightParen);
+    if let (Some(open), Some(close)) = (open, close) {
+        children[..open]
+            .iter()
+            .for_each(|c| render_typed_or_text::<Expr>(c, renderer));
+        // trailing content blocks start on the line of the closing parenthesis
+        let blocks = match &children[close + 1..] {
+            [] => 0,
+            _ if renderer.flat => 0,
+            rest => first_line_width(rest, renderer),
+        };
+        with_trailing(renderer, blocks + renderer.trailing, |renderer| {
+            render_group(&children[open..=close], renderer, group)
+        });
+        children[close + 1..]
+            .iter()
+            .for_each(|c| render_typed_or_text::<Expr>(c, renderer));
+    } else {
+        // only trailing content blocks
+        render_children_typed_or_text_untyped::<Expr>(node, renderer);
+    }
+}
+
+/// Lay out the cells of a `table` or `grid` call as rows of its columns, with the cells of each
+/// column aligned, e.g.
+///
+/// ```typst
+/// #table(
+///   columns: 3,
+///   [a],    [b], [c],
+///   [long], [d], [e],
+/// )
+/// ```
+///
+/// Only applies when enabled, the call has a literal number of columns given before its cells and
+/// every cell fits on a single line.
+/// Returns whether the arguments were rendered, as otherwise nothing is written.
+/// Whether a function is called in math, e.g. `vec` in `$vec(1, 2)$` or `arrow.r` in
+/// `$arrow.r(x)$`.
+fn is_math_callee(callee: Expr) -> bool {
+    match callee {
+        Expr::MathIdent(_) => true,
+        Expr::FieldAccess(access) => is_math_callee(access.target()),
+        _ => false,
+    }
+}
+
+fn render_table_args(callee: Expr, args: &SyntaxNode, renderer: &mut Renderer) -> bool {
+    if renderer.flat || !renderer.writer.config().align_table_columns || !is_table(callee) {
+        return false;
+    }
+    let children: Vec<_> = args.children().collect();
+    if children.first().map(|c| c.kind()) != Some(SyntaxKind::LeftParen) {
+        return false;
+    }
+    let Some(close) = children
+        .iter()
+        .position(|c| c.kind() == SyntaxKind::RightParen)
+    else {
+        return false;
+    };
+    let items: Vec<_> = children[1..close]
+        .iter()
+        .filter(|c| !matches!(c.kind(), SyntaxKind::Space | SyntaxKind::Comma))
+        .collect();
+
+    // the cells have to come after the named arguments so that they make up whole rows
+    let named = items
+        .iter()
+        .take_while(|item| item.kind() == SyntaxKind::Named)
+        .count();
+    let Some(columns) = items[..named].iter().find_map(|item| table_columns(item)) else {
+        return false;
+    };
+    let cells = &items[named..];
+    if cells.is_empty() || !cells.iter().all(|cell| is_table_cell(cell)) {
+        return false;
+    }
+    let Some(texts) = cells
+        .iter()
+        .map(|cell| flat_text(renderer, |trial| render_group_item(cell, trial)))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return false;
+    };
+    debug!(columns, cells = texts.len(), "render_table_args");
+
+    let mut widths = vec![0; columns];
+    for (i, text) in texts.iter().enumerate() {
+        widths[i % columns] = widths[i % columns].max(text.chars().count());
+    }
+    let trailing_comma = renderer.writer.config().trailing_comma != TrailingComma::Never;
+
+    // rows that don't fit are left to the usual layout of arguments
+    let indent = renderer.writer.line_indent() + renderer.writer.config().indent_width();
+    let fits = texts.chunks(columns).all(|cells| {
+        let (last, aligned) = cells.split_last().unwrap();
+        let width: usize = widths[..aligned.len()].iter().map(|width| width + 2).sum();
+        // leaving room for a comma after the last cell
+        indent + width + last.chars().count() < renderer.writer.config().max_width
+    });
+    if !fits {
+        debug!("aligned rows would be too wide");
+        return false;
+    }
+
+    renderer.writer.open_grouping("(").newline();
+    for item in &items[..named] {
+        render_group_item(item, renderer);
+        renderer.writer.push(",").newline();
+    }
+    let rows = texts.len().div_ceil(columns);
+    for (row, cells) in texts.chunks(columns).enumerate() {
+        for (column, text) in cells.iter().enumerate() {
+            renderer.writer.push(text);
+            if column + 1 < cells.len() {
+                let padding = widths[column] - text.chars().count() + 1;
+                renderer.writer.push(",").push(&" ".repeat(padding));
+            } else if row + 1 < rows || trailing_comma {
+                renderer.writer.push(",");
+            }
+        }
+        renderer.writer.newline();
+    }
+    renderer.writer.close_grouping(")");
+    children[close + 1..]
+        .iter()
+        .for_each(|c| render_typed_or_text::<Expr>(c, renderer));
+    true
+}
+
+/// Whether the callee is the `table` or `grid` function.
+fn is_table(callee: Expr) -> bool {
+    let name = match callee {
+        Expr::Ident(ident) => ident.get().clone(),
+        Expr::FieldAccess(access) => access.field().get().clone(),
+        _ => return false,
+    };
+    matches!(name.as_str(), "table" | "grid")
+}
+
+/// The number of columns given by a literal `columns` argument.
+fn table_columns(node: &SyntaxNode) -> Option<usize> {
+    let named = node.cast::<Named>()?;
+    if named.name().as_str() != "columns" {
+        return None;
+    }
+    match named.expr() {
+        Expr::Int(int) => usize::try_from(int.get()).ok().filter(|&n| n > 0),
+        Expr::Array(array) => {
+            let items: Vec<_> = array.items().collect();
+            let all_positional = items.iter().all(|item| matches!(item, ArrayItem::Pos(_)));
+            (all_positional && !items.is_empty()).then_some(items.len())
         }
+        _ => None,
+    }
+}
+
+/// Whether the argument is a single cell, rather than something like `table.header(..)` that can
+/// take up more of the table or a comment.
+fn is_table_cell(node: &SyntaxNode) -> bool {
+    match node.cast::<Expr>() {
+        Some(Expr::FuncCall(call)) => {
+            !matches!(call.callee(), Expr::FieldAccess(access) if is_table(access.target()))
+        }
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Render with the given width of text to follow on the line.
+fn with_trailing(renderer: &mut Renderer, width: usize, render: impl FnOnce(&mut Renderer)) {
+    let outer = std::mem::replace(&mut renderer.trailing, width);
+    render(renderer);
+    renderer.trailing = outer;
+}
+
+/// The width that the nodes take up on the line they start on, when laid out flat if they can be
+/// or else as they were written.
+fn first_line_width(nodes: &[&SyntaxNode], renderer: &Renderer) -> usize {
+    let text = flat_text(renderer, |trial| {
+        nodes
+            .iter()
+            .for_each(|node| render_typed_or_text::<Expr>(node, trial))
+    })
+    .unwrap_or_else(|| {
+        let written: String = nodes
+            .iter()
+            .map(|node| (*node).clone().into_text().to_string())
+            .collect();
+        written.lines().next().unwrap_or_default().to_owned()
+    });
+    text.chars().count()
+}
+
+/// What the render function writes when laid out on a single line, if it fits on one.
+fn flat_text(renderer: &Renderer, render: impl FnOnce(&mut Renderer)) -> Option<String> {
+    let mut trial = Renderer {
+        writer: Writer::new(renderer.writer.config().clone()),
+        flat: true,
+        skip_first: false,
+        keep_erroneous: false,
+        trailing: 0,
+    };
+    render(&mut trial);
+    trial.writer.flat_width()?;
+    let text = trial.writer.finish();
+    Some(text.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+fn render_params(node: &SyntaxNode, renderer: &mut Renderer) {
+    debug!(?node, "render_params");
+    let children: Vec<_> = node.children().collect();
+    if children.first().map(|c| c.kind()) == Some(SyntaxKind::LeftParen) {
+        render_group(&children, renderer, Group::Params);
+    } else {
+        // a single parameter without parentheses, e.g. `x => x`
+        render_children_typed_or_text_untyped::<Pattern>(node, renderer);
     }
 }
@@ -0,0 +1,114 @@
+use typstfmt::Diagnostic;
+use typstfmt::Difference;
+use typstfmt::FormatError;
+
+/// Print the details of a formatting error to stderr, in the style of compiler diagnostics.
+pub fn report(name: &str, error: &FormatError) {
+    eprintln!("error: {error}");
+    match error {
+        FormatError::ErroneousInput(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{}", render_diagnostic(name, diagnostic));
+            }
+        }
+        FormatError::ProducedErroneousOutput { errors, difference } => {
+            // the errors point into the output, which never got written anywhere
+            let output_name = format!("{name}.formatted");
+            for diagnostic in errors {
+                eprintln!("{}", render_diagnostic(&output_name, diagnostic));
+            }
+            if let Some(difference) = difference {
+                eprintln!("note: the first change made to {name}");
+                eprintln!("{}", render_difference(name, difference));
+            }
+        }
+        FormatError::ChangedMeaning { before, after } => {
+            eprintln!("{}", render_diagnostic(name, before));
+            eprintln!("{}", render_diagnostic(&format!("{name}.formatted"), after));
+        }
+        FormatError::FailedToFindFixedPoint { difference } => {
+            if let Some(difference) = difference {
+                eprintln!("note: the first change made when formatting {name} again");
+                eprintln!(
+                    "{}",
+                    render_difference(&format!("{name}.formatted"), difference)
+                );
+            }
+        }
+        FormatError::InvalidRange => {}
+    }
+}
+
+/// Show the diagnostic under the line it points at, e.g.
+///
+/// ```text
+///  --> doc.typ:1:7
+///   |
+/// 1 | #f(a,b]
+///   |       ^ unclosed delimiter
+/// ```
+fn render_diagnostic(name: &str, diagnostic: &Diagnostic) -> String {
+    let number = diagnostic.line.to_string();
+    let gutter = " ".repeat(number.len());
+    // underline as much of the erroneous code as is on the first line
+    let mut bytes = 0;
+    let width = diagnostic
+        .source_line
+        .chars()
+        .skip(diagnostic.column - 1)
+        .take_while(|c| {
+            bytes += c.len_utf8();
+            bytes <= diagnostic.range.len()
+        })
+        .count()
+        .max(1);
+    format!(
+        "{gutter}--> {name}:{}:{}\n{gutter} |\n{number} | {}\n{gutter} | {}{} {}",
+        diagnostic.line,
+        diagnostic.column,
+        diagnostic.source_line,
+        " ".repeat(diagnostic.column - 1),
+        "^".repeat(width),
+        diagnostic.message
+    )
+}
+
+/// Show the lines of the region as a diff, e.g.
+///
+/// ```text
+///  --> doc.typ:3
+///   |
+///   - #let x=1
+///   + #let x = 1
+/// ```
+fn render_difference(name: &str, difference: &Difference) -> String {
+    let gutter = " ".repeat(difference.line.to_string().len());
+    let mut rendered = format!("{gutter}--> {name}:{}\n{gutter} |", difference.line);
+    for line in difference.before.lines() {
+        rendered.push_str(&format!("\n{gutter} - {line}"));
+    }
+    for line in difference.after.lines() {
+        rendered.push_str(&format!("\n{gutter} + {line}"));
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnostic_underlines_code() {
+        let diagnostic = Diagnostic {
+            message: "expected expression".to_owned(),
+            range: 9..13,
+            line: 12,
+            column: 10,
+            source_line: "#let α = (1,2".to_owned(),
+        };
+        assert_eq!(
+            render_diagnostic("doc.typ", &diagnostic),
+            "  --> doc.typ:12:10\n   |\n12 | #let α = (1,2\n   |          ^^^^ expected expression"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use ignore::gitignore::Gitignore;
+use ignore::gitignore::GitignoreBuilder;
+use ignore::overrides::Override;
+use ignore::overrides::OverrideBuilder;
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// The name of the files listing paths not to format, in the same format as a `.gitignore`.
+const IGNORE_FILE_NAME: &str = ".typstfmtignore";
+
+/// The options of a config file for which files to format when walking a directory.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WalkConfig {
+    /// Glob patterns of the files to format when walking a directory.
+    pub include: Vec<String>,
+    /// Glob patterns of the files and directories to skip when walking a directory.
+    pub exclude: Vec<String>,
+}
+
+impl Default for WalkConfig {
+    fn default() -> Self {
+        WalkConfig {
+            include: vec!["**/*.typ".to_owned()],
+            exclude: Vec::new(),
+        }
+    }
+}
+
+/// Find the files to format in the directory.
+///
+/// Files have to match one of the config's `include` patterns, and not be excluded by its
+/// `exclude` patterns, the extra `exclude` patterns, a `.typstfmtignore`, an `.ignore` or, inside a
+/// git repository, its `.gitignore` files.
+/// Hidden files and directories are skipped too.
+/// Patterns are matched against paths relative to the directory.
+pub fn typst_files(
+    root: &Path,
+    config: &WalkConfig,
+    exclude: &[String],
+) -> Result<Vec<PathBuf>, ignore::Error> {
+    let (includes, excludes) = patterns(root, config, exclude)?;
+
+    // ignore globs in overrides stop the walk from going into excluded directories at all
+    let walk = WalkBuilder::new(root)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        // a consistent order keeps the output the same between runs and machines
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .overrides(excludes)
+        .build();
+    let mut files = Vec::new();
+    for entry in walk {
+        match entry {
+            Ok(entry) => {
+                let is_file = entry.file_type().is_some_and(|t| t.is_file());
+                if is_file && includes.matched(entry.path(), false).is_whitelist() {
+                    files.push(entry.into_path());
+                }
+            }
+            Err(error) => warn!(%error, "Failed to walk directory"),
+        }
+    }
+    Ok(files)
+}
+
+/// Whether the file would be skipped when walking the directory, by the same rules as
+/// [`typst_files`].
+///
+/// The file doesn't need to exist, such as for unsaved content from an editor.
+pub fn is_excluded(
+    root: &Path,
+    file: &Path,
+    config: &WalkConfig,
+    exclude: &[String],
+) -> Result<bool, ignore::Error> {
+    let file = normalise(file);
+    let file = file.as_path();
+    let (includes, excludes) = patterns(root, config, exclude)?;
+    if !includes.matched(file, false).is_whitelist() {
+        return Ok(true);
+    }
+
+    // the file is skipped if it, or any directory it is in, would be
+    let relative = file.strip_prefix(root).unwrap_or(file);
+    let mut path = root.to_owned();
+    for component in relative.components() {
+        path.push(component);
+        let is_dir = path != file;
+        let hidden =
+            matches!(component, Component::Normal(name) if name.to_string_lossy().starts_with('.'));
+        if hidden || excludes.matched(&path, is_dir).is_ignore() {
+            return Ok(true);
+        }
+    }
+
+    // as when walking, `.typstfmtignore` files come before `.ignore` files, which come before the
+    // ignore files of git, and of each kind the nearest one with a matching pattern decides
+    let repo = file
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join(".git").exists());
+    let mut ignores = Vec::new();
+    for name in [IGNORE_FILE_NAME, ".ignore", ".gitignore"] {
+        for dir in file.ancestors().skip(1) {
+            // `.gitignore` files only count within a repository
+            if name == ".gitignore" && repo.is_none() {
+                break;
+            }
+            let ignore_file = dir.join(name);
+            if ignore_file.is_file() {
+                ignores.push(Gitignore::new(ignore_file).0);
+            }
+            if name == ".gitignore" && repo == Some(dir) {
+                break;
+            }
+        }
+    }
+    if let Some(repo) = repo {
+        let mut exclude = GitignoreBuilder::new(repo);
+        exclude.add(repo.join(".git/info/exclude"));
+        ignores.extend(exclude.build());
+        ignores.push(Gitignore::global().0);
+    }
+    let matched = ignores
+        .iter()
+        .map(|ignore| ignore.matched_path_or_any_parents(file, false))
+        .find(|matched| !matched.is_none());
+    Ok(matched.is_some_and(|matched| matched.is_ignore()))
+}
+
+/// The path without any `.` or `..` components, going by its text alone.
+fn normalise(path: &Path) -> PathBuf {
+    let mut normalised = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalised.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalised.pop();
+            }
+            _ => normalised.push(component),
+        }
+    }
+    normalised
+}
+
+/// The patterns of files to include, and those to exclude as ignore globs.
+fn patterns(
+    root: &Path,
+    config: &WalkConfig,
+    exclude: &[String],
+) -> Result<(Override, Override), ignore::Error> {
+    let mut includes = OverrideBuilder::new(root);
+    for pattern in &config.include {
+        includes.add(pattern)?;
+    }
+    let mut excludes = OverrideBuilder::new(root);
+    for pattern in config.exclude.iter().chain(exclude) {
+        excludes.add(&format!("!{pattern}"))?;
+    }
+    Ok((includes.build()?, excludes.build()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn relative_paths_are_not_hidden() {
+        let root = std::env::temp_dir().join(format!("typstfmt-walk-{}", std::process::id()));
+        let cwd = root.join("cwd");
+        fs::create_dir_all(&cwd).unwrap();
+        let config = WalkConfig::default();
+
+        let excluded = |path: &str| is_excluded(&cwd, &cwd.join(path), &config, &[]).unwrap();
+        assert!(!excluded("../proj/doc.typ"));
+        assert!(!excluded("./doc.typ"));
+        assert!(excluded(".hidden/doc.typ"));
+        assert!(excluded("../.hidden/doc.typ"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn gitignore_only_applies_in_a_repository() {
+        let root = std::env::temp_dir().join(format!("typstfmt-gitignore-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join(".gitignore"), "ignored.typ\n").unwrap();
+        fs::write(root.join("ignored.typ"), "").unwrap();
+        let config = WalkConfig::default();
+        let file = root.join("ignored.typ");
+
+        assert!(!is_excluded(&root, &file, &config, &[]).unwrap());
+        assert_eq!(
+            typst_files(&root, &config, &[]).unwrap(),
+            [root.join("ignored.typ")]
+        );
+
+        fs::create_dir(root.join(".git")).unwrap();
+        assert!(is_excluded(&root, &file, &config, &[]).unwrap());
+        assert!(typst_files(&root, &config, &[]).unwrap().is_empty());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::config::Config;
+use crate::config::{Config, IndentStyle, NewlineStyle};
 use tracing::debug;
 
 /// A context object used to store state while formatting.
@@ -10,10 +10,12 @@ pub struct Writer {
     config: Config,
 
     line: String,
-    /// The current indentation level, in spaces.
+    /// The current indentation level, in columns.
     current_indent_level: usize,
     next_indent_level: usize,
     last_indent: LastIndentChange,
+    /// Whether the current line continues verbatim text, so shouldn't be indented.
+    verbatim: bool,
 }
 
 #[derive(Default, PartialEq, PartialOrd)]
@@ -34,37 +36,85 @@ impl Writer {
             current_indent_level: 0,
             next_indent_level: 0,
             last_indent: LastIndentChange::None,
+            verbatim: false,
+        }
+    }
+
+    /// Create a new writer with the given config, starting at the given indentation level.
+    pub fn with_indent(config: Config, level: usize) -> Self {
+        Self {
+            current_indent_level: level,
+            next_indent_level: level,
+            ..Self::new(config)
         }
     }
 
     fn flush_line(&mut self) {
         if !self.line.is_empty() {
-            let line = format!(
-                "{}{}",
-                self.current_indent(),
-                std::mem::take(&mut self.line)
-            );
+            let indent = if self.verbatim {
+                String::new()
+            } else {
+                self.current_indent()
+            };
+            let line = format!("{}{}", indent, std::mem::take(&mut self.line));
             debug!(?line, "flushing line");
             self.value.push_str(&line);
         } else {
             debug!("flushing empty line");
         }
         self.value.push('\n');
+        self.verbatim = false;
         self.current_indent_level = self.next_indent_level;
         self.last_indent = LastIndentChange::None;
     }
 
     /// Push the current indentation amount.
     fn current_indent(&self) -> String {
-        " ".repeat(self.current_indent_level)
+        indentation(&self.config, self.current_indent_level)
+    }
+
+    /// The config in use by this writer.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The width of the current line so far, including its indentation.
+    pub fn current_width(&self) -> usize {
+        self.line_indent() + self.line.chars().count()
+    }
+
+    /// The indentation of the current line, in columns.
+    pub fn line_indent(&self) -> usize {
+        if self.verbatim {
+            0
+        } else {
+            self.current_indent_level
+        }
+    }
+
+    /// Whether nothing has been written on the current line yet.
+    pub fn at_line_start(&self) -> bool {
+        self.line.is_empty()
+    }
+
+    /// The width of everything written, if it all fits on a single line.
+    pub fn flat_width(&self) -> Option<usize> {
+        if self.value.is_empty() {
+            Some(self.line.chars().count())
+        } else {
+            None
+        }
     }
 
     /// Appends the given text to the buffer.
     pub fn push(&mut self, s: &str) -> &mut Self {
         debug!(?s, "push");
-        for c in s.chars() {
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
             if c == '\n' {
                 self.flush_line();
+            } else if c == '\r' && chars.peek() == Some(&'\n') {
+                // the line ending is written when finishing
             } else {
                 self.line.push(c);
             }
@@ -79,14 +129,56 @@ impl Writer {
         self
     }
 
-    pub fn parbreak(&mut self) -> &mut Self {
+    /// Appends the given text to the buffer exactly as it is, without indenting any of its lines.
+    pub fn push_verbatim(&mut self, s: &str) -> &mut Self {
+        debug!(?s, "push_verbatim");
+        let mut lines = s.split('\n');
+        if let Some(first) = lines.next() {
+            self.line.push_str(first);
+        }
+        for line in lines {
+            self.flush_line();
+            self.line.push_str(line);
+            self.verbatim = !line.is_empty();
+        }
+        self
+    }
+
+    /// Ends the current line and makes sure the given number of blank lines follow it, up to
+    /// `max_blank_lines`.
+    pub fn blank_lines(&mut self, count: usize) -> &mut Self {
+        let count = count.min(self.max_blank_lines());
+        self.exact_blank_lines(count)
+    }
+
+    /// Ends the current line and makes sure the given number of blank lines follow it, even past
+    /// `max_blank_lines`, for spacing the config asks for explicitly.
+    pub fn exact_blank_lines(&mut self, count: usize) -> &mut Self {
         if !self.line.is_empty() {
             self.newline();
         }
-        self.newline();
+        while self.trailing_blank_lines() < count {
+            self.push("\n");
+        }
         self
     }
 
+    /// The number of blank lines written since the last line with content.
+    fn trailing_blank_lines(&self) -> usize {
+        let newlines = self.value.len() - self.value.trim_end_matches('\n').len();
+        if newlines == self.value.len() {
+            newlines
+        } else {
+            newlines - 1
+        }
+    }
+
+    /// The most blank lines to write in a row, which is at least the one that separates
+    /// paragraphs, so a `max_blank_lines` of zero keeps them too.
+    fn max_blank_lines(&self) -> usize {
+        self.config.max_blank_lines.max(1)
+    }
+
     pub fn space(&mut self) -> &mut Self {
         if self.line.is_empty() {
             // never push a space after a newline
@@ -95,8 +187,12 @@ impl Writer {
         self.push(" ")
     }
 
-    /// Appends a newline character to the buffer.
+    /// Appends a newline character to the buffer, unless it would make too many blank lines.
     pub fn newline(&mut self) -> &mut Self {
+        if self.line.is_empty() && self.trailing_blank_lines() >= self.max_blank_lines() {
+            debug!("skipping blank line");
+            return self;
+        }
         self.push("\n");
         self
     }
@@ -105,7 +201,9 @@ impl Writer {
     pub fn inc_indent(&mut self) -> &mut Self {
         debug!("inc_indent");
         if self.last_indent != LastIndentChange::Inc {
-            self.next_indent_level = self.next_indent_level.saturating_add(self.config.indent);
+            self.next_indent_level = self
+                .next_indent_level
+                .saturating_add(self.config.indent_width());
             self.last_indent = LastIndentChange::Inc;
         }
         self
@@ -115,7 +213,9 @@ impl Writer {
     pub fn dec_indent(&mut self, invisible: bool) -> &mut Self {
         debug!("dec_indent");
         if invisible || self.last_indent != LastIndentChange::Dec {
-            self.next_indent_level = self.next_indent_level.saturating_sub(self.config.indent);
+            self.next_indent_level = self
+                .next_indent_level
+                .saturating_sub(self.config.indent_width());
             self.current_indent_level = self.next_indent_level;
             if !invisible {
                 self.last_indent = LastIndentChange::Dec;
@@ -124,6 +224,20 @@ impl Writer {
         self
     }
 
+    /// Indents the lines after the current one by the given number of columns more.
+    pub fn indent_by(&mut self, columns: usize) -> &mut Self {
+        debug!(columns, "indent_by");
+        self.next_indent_level = self.next_indent_level.saturating_add(columns);
+        self
+    }
+
+    /// Undoes [`indent_by`](Self::indent_by) for the lines after the current one.
+    pub fn dedent_by(&mut self, columns: usize) -> &mut Self {
+        debug!(columns, "dedent_by");
+        self.next_indent_level = self.next_indent_level.saturating_sub(columns);
+        self
+    }
+
     pub fn open_grouping(&mut self, text: &str) -> &mut Self {
         debug!(?text, "open grouping");
         self.push(text).inc_indent();
@@ -137,15 +251,64 @@ impl Writer {
         self
     }
 
-    /// Get the written value.
+    /// End a whole document, with the configured number of blank lines at its start and end.
+    pub fn end_document(&mut self) -> &mut Self {
+        if !self.line.is_empty() {
+            self.flush_line();
+        }
+        let content = self.value.trim_matches(['\r', '\n']);
+        self.value = if content.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "{}{}\n{}",
+                "\n".repeat(self.config.blank_lines_at_start),
+                content,
+                "\n".repeat(self.config.blank_lines_at_end)
+            )
+        };
+        self
+    }
+
+    /// Get the written value, with every line ended in the configured style.
     pub fn finish(mut self) -> String {
         if !self.line.is_empty() {
             self.flush_line();
         }
-        self.value
+        // verbatim and raw text can bring their own line endings along
+        let value = self.value.replace("\r\n", "\n");
+        match self.config.newline_style.resolve(&value) {
+            NewlineStyle::Crlf => value.replace('\n', "\r\n"),
+            _ => value,
+        }
     }
 }
 
+/// The text to indent a line by the given number of columns.
+///
+/// With tabs, any columns left over from a whole tab are made up with spaces.
+pub(crate) fn indentation(config: &Config, columns: usize) -> String {
+    match config.indent_style {
+        IndentStyle::Spaces => " ".repeat(columns),
+        IndentStyle::Tabs => {
+            let tab_width = config.tab_width.max(1);
+            format!(
+                "{}{}",
+                "\t".repeat(columns / tab_width),
+                " ".repeat(columns % tab_width)
+            )
+        }
+    }
+}
+
+/// The number of columns taken up by the whitespace.
+pub(crate) fn indentation_width(config: &Config, whitespace: &str) -> usize {
+    whitespace
+        .chars()
+        .map(|c| if c == '\t' { config.tab_width } else { 1 })
+        .sum()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,4 +341,54 @@ mod tests {
             format!("f(\n{0}a,\n{0}b,\n)\n", " ".repeat(indent))
         );
     }
+
+    #[test]
+    fn verbatim() {
+        let mut writer = Writer::default();
+        writer
+            .inc_indent()
+            .newline()
+            .push_verbatim("a(\n b,\n   c)")
+            .push(",")
+            .newline()
+            .push("d");
+        similar_asserts::assert_eq!(writer.finish(), "\n  a(\n b,\n   c),\n  d\n");
+    }
+
+    #[test]
+    fn blank_lines() {
+        let config = Config {
+            max_blank_lines: 2,
+            blank_lines_at_start: 1,
+            ..Config::default()
+        };
+        let mut writer = Writer::new(config);
+        writer
+            .newline()
+            .push("a")
+            .blank_lines(3)
+            .push("b")
+            .blank_lines(1)
+            .newline()
+            .newline()
+            .newline()
+            .push("c")
+            .blank_lines(1)
+            .end_document();
+        similar_asserts::assert_eq!(writer.finish(), "\na\n\n\nb\n\n\nc\n");
+    }
+
+    #[test]
+    fn tabs() {
+        let config = Config {
+            indent_style: IndentStyle::Tabs,
+            ..Config::default()
+        };
+        let mut writer = Writer::new(config);
+        writer.push("f(").inc_indent().newline().push("a,");
+        // a tab is as wide as `tab_width` spaces
+        assert_eq!(writer.current_width(), 6);
+        writer.newline().dec_indent(false).push(")");
+        similar_asserts::assert_eq!(writer.finish(), "f(\n\ta,\n)\n");
+    }
 }
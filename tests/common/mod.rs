@@ -1,8 +1,19 @@
+#[allow(unused_macros)]
+macro_rules! test_config {
+    () => {
+        typstfmt::Config::default()
+    };
+    ($config:expr) => {
+        $config
+    };
+}
+
 #[allow(unused_macros)]
 macro_rules! test_snippet {
     (
         $test_name:ident,
         $(ignore = $ignore:tt ,)?
+        $(config = $config:expr ,)?
         expect = $expected:expr,
         $snippet:expr,
     ) => {
@@ -10,11 +21,12 @@ macro_rules! test_snippet {
         $(#[ignore = $ignore])?
         fn $test_name() {
             let _ = tracing_subscriber::fmt().with_test_writer().with_max_level(tracing::Level::DEBUG).try_init();
+            let config = test_config!($($config)?);
             let snippet = $snippet.trim_start();
             let expected = $expected.trim_start();
-            let formatted = typstfmt::format(snippet, typstfmt::Config::default()).unwrap();
+            let formatted = typstfmt::format(snippet, config.clone()).unwrap();
             similar_asserts::assert_eq!(formatted, expected, "first format");
-            let reformatted = typstfmt::format(&formatted, typstfmt::Config::default()).unwrap();
+            let reformatted = typstfmt::format(&formatted, config).unwrap();
             similar_asserts::assert_eq!(reformatted, expected, "second format");
         }
     };
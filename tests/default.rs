@@ -80,10 +80,19 @@ test_snippet!(
     arg_indent,
     expect = r"
 #let f(
-  a,
-  b,
+  first-argument,
+  second-argument,
+  third-argument,
+  fourth-argument,
+  fifth-argument,
 ) = a + b
 ",
+    "#let f(\nfirst-argument,\nsecond-argument,\nthird-argument,\nfourth-argument,\nfifth-argument,\n) = a + b",
+);
+
+test_snippet!(
+    arg_join,
+    expect = "#let f(a, b) = a + b\n",
     "#let f(\na,\nb,\n) = a + b",
 );
 
@@ -131,8 +140,7 @@ Best,"#,
 test_snippet! {
     function_content_arg_multiline,
     expect = r##"#{
-  f(
-  )[
+  f()[
     /* starts
      * the body
      */
@@ -344,9 +352,7 @@ test_snippet! {
 test_snippet! {
     multiline_nested,
     expect = r#"
-#block(block(block(
-  width: 100%,
-)))
+#block(block(block(width: 100%)))
 "#,
     r#"
 #block(block(block(
@@ -358,10 +364,7 @@ test_snippet! {
 test_snippet! {
     markup_indent,
     expect = r#"
-#f(
-  g([]),
-  a,
-)
+#f(g([]), a)
 "#,
     r#"
 #f(
@@ -406,3 +409,714 @@ test_snippet! {
 "#,
 }
 
+test_snippet! {
+    long_args_wrap,
+    expect = r#"
+#set text(
+  font: "New Computer Modern",
+  size: 11pt,
+  lang: "en",
+  region: "gb",
+  hyphenate: true,
+)
+"#,
+    r#"
+#set text(font: "New Computer Modern", size: 11pt, lang: "en", region: "gb", hyphenate: true)
+"#,
+}
+
+test_snippet! {
+    long_dict_wrap,
+    expect = r##"
+#let colours = (
+  primary: rgb("#1f77b4"),
+  secondary: rgb("#ff7f0e"),
+  tertiary: rgb("#2ca02c"),
+)
+"##,
+    r##"
+#let colours = (primary: rgb("#1f77b4"), secondary: rgb("#ff7f0e"), tertiary: rgb("#2ca02c"))
+"##,
+}
+
+test_snippet!(
+    array_spacing,
+    expect = "#let a = (..xs, 1, (2, 3), (4,))\n",
+    "#let a = ( ..xs,1 ,(2,3),(4 ,) )",
+);
+
+test_snippet! {
+    long_array_wrap,
+    expect = r##"
+#let data = (
+  // header
+  ("name", "value"),
+  ("alpha", 1), /* the first */
+  ("beta", 2),
+  ..rest,
+)
+"##,
+    r##"
+#let data = (// header
+("name","value"),("alpha",1), /* the first */
+  ("beta",2), ..rest)
+"##,
+}
+
+test_snippet! {
+    long_nested_hug,
+    expect = r#"
+#block(block(
+  width: 100%,
+  height: 100%,
+  fill: red,
+  stroke: blue,
+  inset: 10pt,
+  outset: 2pt,
+))
+"#,
+    r#"
+#block(block(width: 100%, height: 100%, fill: red, stroke: blue, inset: 10pt, outset: 2pt))
+"#,
+}
+
+test_snippet! {
+    max_width_config,
+    config = typstfmt::Config { max_width: 20, ..Default::default() },
+    expect = r#"
+#f(a: 1, b: 2)
+#f(
+  a: 1,
+  b: 2,
+  c: 3,
+  d: 4,
+)
+"#,
+    r#"
+#f(a: 1, b: 2)
+#f(a: 1, b: 2, c: 3, d: 4)
+"#,
+}
+
+test_snippet! {
+    indent_with_tabs,
+    config = typstfmt::Config { indent_style: typstfmt::IndentStyle::Tabs, ..Default::default() },
+    expect = "
+#let f(a, b) = {
+\t/* a comment
+\t * over lines */
+\ta + b
+}
+- item that
+\tcontinues
+#f(
+\taaaaaaaaaaaaaaaaaaaaaaaa,
+\tbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb,
+\tcccccccccccccccccccccccccccccc,
+)
+",
+    "
+#let f(a, b) = {
+  /* a comment
+  over lines */
+  a + b
+}
+- item that
+  continues
+#f(aaaaaaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccccccccccccc)
+",
+}
+
+test_snippet! {
+    directive_off_on,
+    expect = r#"
+#let a = 1
+// typstfmt: off
+#let   b   =   (
+      1,2,
+  3,4)
+// typstfmt: on
+#let c = 3
+"#,
+    r#"
+#let a=1
+// typstfmt: off
+#let   b   =   (
+      1,2,
+  3,4)
+// typstfmt: on
+#let c=3
+"#,
+}
+
+test_snippet! {
+    directive_off_in_code,
+    expect = r#"
+#{
+  let a = 1
+  // typstfmt: off
+  let   b   =   (
+        1,2,
+    3,4)
+  // typstfmt: on
+  let c = 3
+}
+"#,
+    r#"
+#{
+let a=1
+    // typstfmt: off
+  let   b   =   (
+        1,2,
+    3,4)
+  // typstfmt: on
+let c=3
+}
+"#,
+}
+
+test_snippet! {
+    directive_skip,
+    expect = r#"
+// typstfmt: skip
+#table(columns: 2,
+  [a],   [b],
+  [cc],  [d],
+)
+#f(a, b)
+"#,
+    r#"
+// typstfmt: skip
+#table(columns: 2,
+  [a],   [b],
+  [cc],  [d],
+)
+#f(a,b)
+"#,
+}
+
+test_snippet! {
+    directive_skip_in_code,
+    expect = r#"
+#{
+  // typstfmt: skip
+  let x = (1,0,
+           0,1)
+
+  let y = f(1, 2)
+}
+"#,
+    r#"
+#{
+  // typstfmt: skip
+  let x = (1,0,
+           0,1)
+
+  let y = f(1,2)
+}
+"#,
+}
+
+test_snippet! {
+    code_comment_between,
+    expect = r"
+#{
+  let a = 1
+  // c
+  let b = 2
+
+  // d
+
+  let c = 3
+}
+",
+    r"
+#{
+let a=1
+// c
+let b=2
+
+// d
+
+let c=3
+}
+",
+}
+
+test_snippet!(
+    crlf_preserved,
+    expect = "#let f(a, b) = {\r\n  a + b\r\n}\r\n\r\n```\r\nraw\r\n```\r\n",
+    "#let f(a,b) = {\r\na+b\r\n}\r\n\r\n\r\n```\r\nraw\r\n```\r\n",
+);
+
+test_snippet!(
+    crlf_code_blank_lines,
+    expect = "#{\r\n  let a = 1\r\n\r\n  let b = 2\r\n  a + b\r\n}\r\n",
+    "#{\r\nlet a=1\r\n\r\n\r\nlet b=2\r\na+b\r\n}\r\n",
+);
+
+test_snippet!(
+    crlf_to_lf,
+    config = typstfmt::Config {
+        newline_style: typstfmt::NewlineStyle::Lf,
+        ..Default::default()
+    },
+    expect = "#let f(a, b) = {\n  a + b\n}\n\n```\nraw\n```\n",
+    "#let f(a,b) = {\r\na+b\r\n}\r\n\r\n```\r\nraw\r\n```\r\n",
+);
+
+test_snippet!(
+    lf_to_crlf,
+    config = typstfmt::Config {
+        newline_style: typstfmt::NewlineStyle::Crlf,
+        ..Default::default()
+    },
+    expect = "/* a\r\n * comment */\r\n#f(a)\r\n",
+    "/* a\n   comment */\n#f(a,\n)\n",
+);
+
+test_snippet!(
+    trailing_comma_always,
+    config = typstfmt::Config {
+        trailing_comma: typstfmt::TrailingComma::Always,
+        ..Default::default()
+    },
+    expect = "#let g(x, y,) = (x, y,)\n#let d = (a: 1, b: 2,)\n",
+    "#let g(x,y) = (x,y)\n#let d = (a:1,b:2)\n",
+);
+
+test_snippet!(
+    trailing_comma_always_leaves_math_arguments,
+    config = typstfmt::Config {
+        trailing_comma: typstfmt::TrailingComma::Always,
+        ..Default::default()
+    },
+    expect = "$vec(x, y) + vec(x, y,)$\n",
+    "$vec(x,y) + vec(x,y,)$\n",
+);
+
+test_snippet!(
+    trailing_comma_always_leaves_matrix_rows,
+    config = typstfmt::Config {
+        trailing_comma: typstfmt::TrailingComma::Always,
+        ..Default::default()
+    },
+    expect = "$mat(1, 2; 3, 4) + mat(1, 2; 3, 4,)$\n",
+    "$mat(1,2;3,4) + mat(1,2;3,4,)$\n",
+);
+
+test_snippet!(
+    trailing_comma_never,
+    config = typstfmt::Config {
+        trailing_comma: typstfmt::TrailingComma::Never,
+        ..Default::default()
+    },
+    expect = "
+#let single = (x,)
+#f(
+  aaaaaaaaaaaaaaaaaaaaaaaa,
+  bbbbbbbbbbbbbbbbbbbbbbbbbbbbbb,
+  cccccccccccccccccccccccccccccc
+)
+",
+    "
+#let single = (x,)
+#f(aaaaaaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccccccccccccc,)
+",
+);
+
+test_snippet! {
+    table_columns_aligned,
+    config = typstfmt::Config { align_table_columns: true, ..Default::default() },
+    expect = r##"
+#table(
+  columns: 3,
+  align: center,
+  [a],    [bbb], [c],
+  [dddd], [e],   [f],
+  [g],
+)
+#grid(
+  columns: (1fr, auto),
+  [x],   [yy],
+  [zzz], [w],
+)
+#table(columns: 2, table.header[A][B], [a], [b])
+"##,
+    r##"
+#table(columns: 3, align: center, [a], [bbb], [c], [dddd], [e], [f], [g])
+#grid(columns: (1fr, auto), [x],[yy],
+  [zzz],[w])
+#table(columns: 2, table.header[A][B], [a], [b])
+"##,
+}
+
+test_snippet! {
+    math_spacing,
+    expect = r##"
+$a + b = -c$ and $x_1^2 <= f (x)$ and $mat(1, 2; 3, 4)$
+
+$ sum_(i = 0)^n i = (n(n + 1))/2 $
+"##,
+    r##"
+$a+b=- c$ and $x _1 ^2<=f (x)$ and $mat(1,2;3,4)$
+
+$ sum_(i=0)^n i=(n(n+1))/2 $
+"##,
+}
+
+test_snippet! {
+    math_multiline,
+    expect = r##"
+$
+  a &= b + c \
+  &= -d
+$
+#let eq = $
+  x // the first
+  + y
+$
+"##,
+    r##"
+$ a &=b+c \ & = - d $
+#let eq = $ x // the first
+    +y $
+"##,
+}
+
+test_snippet! {
+    math_broken_arguments,
+    expect = r##"
+$ vec(
+  aaaaaaaaaaaaaaaaaaaaaaa,
+  bbbbbbbbbbbbbbbbbbbbbbbbbbb,
+  cccccccccccccccccccccccccccc
+) $
+$ mat(
+  aaaaaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbbb;
+  cccccccccccccccccccccccccccc, dddddddddddddddddd
+) $
+"##,
+    r##"
+$ vec(aaaaaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccccccccccc) $
+$ mat(aaaaaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbbb; cccccccccccccccccccccccccccc, dddddddddddddddddd) $
+"##,
+}
+
+test_snippet! {
+    markup_wrap_fill,
+    config = typstfmt::Config { markup_wrap: typstfmt::MarkupWrap::Fill, max_width: 40, ..Default::default() },
+    expect = r##"
+= A heading that is too long to fit on a line of forty
+
+Some text with *strong words* and
+`raw code` in it, see @ref. A minus - 2.
+is not a list. Then a break \
+after `#f(x)` and
+https://example.org/a/long/link.
+
+#image("a.png")
+#image("b.png")
+"##,
+    r##"
+= A heading that is too long to fit on a line of forty
+
+Some text with *strong words* and `raw code`
+in it, see @ref. A minus - 2. is not a list. Then a break \ after `#f(x)` and https://example.org/a/long/link.
+
+#image("a.png")
+#image("b.png")
+"##,
+}
+
+test_snippet! {
+    markup_wrap_sentence,
+    config = typstfmt::Config { markup_wrap: typstfmt::MarkupWrap::Sentence, ..Default::default() },
+    expect = r##"
+One sentence, e.g. this one.
+Another one (with brackets.)
+And a third \
+split over lines?
+"##,
+    r##"
+One sentence, e.g. this one. Another
+one (with brackets.) And a third \
+split over lines?
+"##,
+}
+
+test_snippet!(
+    markup_wrap_keeps_markers_off_line_starts,
+    config = typstfmt::Config {
+        markup_wrap: typstfmt::MarkupWrap::Fill,
+        max_width: 40,
+        ..Default::default()
+    },
+    expect = "aaaaaaaaaa bbbbbbbbbb cccccccccc dddddd -\ne\n",
+    "aaaaaaaaaa bbbbbbbbbb cccccccccc dddddd - e\n",
+);
+
+test_snippet! {
+    heading_spacing,
+    config = typstfmt::Config { blank_lines_around_headings: Some(1), ..Default::default() },
+    expect = r##"
+= Title
+
+text
+
+== Section
+
+- item
+
+= Other
+
+#[
+  == Nested
+
+  body
+]
+"##,
+    "
+=   Title   
+text
+
+
+
+==  Section
+- item
+= Other
+#[
+  == Nested
+  body
+]
+",
+}
+
+test_snippet!(
+    nested_list_indent,
+    expect = r##"
+- a
+  - b
+    - c
+    more b
+- d
+"##,
+    "
+- a
+    - b
+        - c
+      more b
+- d
+",
+);
+
+test_snippet!(
+    enum_marker_sequential,
+    config = typstfmt::Config {
+        enum_marker: typstfmt::EnumMarker::Sequential,
+        ..Default::default()
+    },
+    expect = r##"
+1. x
+2. y
+  1. nested
+3. z
+
+4. after a break
+
+Text
+1. new
+2. b
+
+3. c
+"##,
+    "
+3. x
+5. y
+   + nested
++ z
+
++ after a break
+
+Text
++ new
++ b
+
+3. c
+",
+);
+
+test_snippet!(
+    enum_marker_keep_first,
+    config = typstfmt::Config {
+        enum_marker: typstfmt::EnumMarker::KeepFirst,
+        ..Default::default()
+    },
+    expect = r##"
+3. x
+4. y
+5. z
+
+6. more
+
+Text
+2. a
+3. b
+"##,
+    "
+3. x
++ y
+7. z
+
++ more
+
+Text
+2. a
+2. b
+",
+);
+
+test_snippet!(
+    enum_marker_plus,
+    config = typstfmt::Config {
+        enum_marker: typstfmt::EnumMarker::Plus,
+        ..Default::default()
+    },
+    expect = r##"
+3. x
++ y
+7. z
+
+1. a
++ b
+"##,
+    "
+3. x
+4. y
+7. z
+
+1. a
+2. b
+",
+);
+
+test_snippet!(
+    term_descriptions_aligned,
+    config = typstfmt::Config { align_term_descriptions: true, ..Default::default() },
+    expect = r##"
+/ a:           short
+/ longer term: another description
+/ mid:         this description is rather long and will need to be wrapped onto
+               the next line because it is long
+
+Text
+/ b:
+  - nested
+"##,
+    "
+/ a: short
+/ longer term: another description
+/ mid: this description is rather long and will need to be wrapped onto the next line because it is long
+
+Text
+/ b:
+  - nested
+",
+);
+
+test_snippet!(
+    blank_lines_limited,
+    config = typstfmt::Config {
+        max_blank_lines: 2,
+        blank_lines_at_end: 1,
+        ..Default::default()
+    },
+    expect = r##"
+text
+
+
+more
+
+#{
+
+
+  let x = 1
+
+
+  x
+}
+
+"##,
+    "
+
+
+
+text
+
+
+
+more
+
+#{
+
+
+
+  let x = 1
+
+
+
+  x
+}
+
+
+",
+);
+
+test_snippet!(
+    heading_spacing_past_max_blank_lines,
+    config = typstfmt::Config {
+        blank_lines_around_headings: Some(2),
+        max_blank_lines: 0,
+        ..Default::default()
+    },
+    expect = "= Title\n\n\ntext\n\nmore\n\n\n== Section\n",
+    "= Title\ntext\n\n\n\nmore\n== Section\n",
+);
+
+test_snippet! {
+    trailing_content_counts_toward_width,
+    expect = r##"
+#figure(
+  image("a-long-file-name.png"),
+  caption: "a caption that is longish",
+)[body text]
+#let y = (
+  aaaaaaaaaaaaaaaa,
+  bbbbbbbbbbbbbbbbbbbb,
+  cccccccccccccccccc,
+).map(it => it + 1)
+#f(a, b)[short]
+"##,
+    r##"
+#figure(image("a-long-file-name.png"), caption: "a caption that is longish")[body text]
+#let y = (aaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbb, cccccccccccccccccc).map(it => it + 1)
+#f(a, b)[short]
+"##,
+}
+
+test_snippet! {
+    table_columns_too_wide,
+    config = typstfmt::Config { align_table_columns: true, ..Default::default() },
+    expect = r##"
+#table(
+  columns: 2,
+  [a fairly long cell of text that takes up space],
+  [another long cell of text here],
+  [a],
+  [b],
+)
+"##,
+    r##"
+#table(columns: 2, [a fairly long cell of text that takes up space], [another long cell of text here], [a], [b])
+"##,
+}
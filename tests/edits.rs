@@ -0,0 +1,54 @@
+use typstfmt::{format, format_edits, Config, TextEdit};
+
+fn apply(input: &str, edits: &[TextEdit]) -> String {
+    let mut output = input.to_owned();
+    for edit in edits.iter().rev() {
+        output.replace_range(edit.range.clone(), &edit.text);
+    }
+    output
+}
+
+fn check(input: &str) -> Vec<TextEdit> {
+    let config = Config::default();
+    let edits = format_edits(input, &config).unwrap();
+    for pair in edits.windows(2) {
+        assert!(pair[0].range.end <= pair[1].range.start, "{edits:?}");
+    }
+    assert_eq!(apply(input, &edits), format(input, config).unwrap());
+    edits
+}
+
+#[test]
+fn edits_are_minimal() {
+    let edits = check("#let x=4\n#let y = 5\n");
+    assert_eq!(
+        edits,
+        vec![
+            TextEdit {
+                range: 6..6,
+                text: " ".to_owned()
+            },
+            TextEdit {
+                range: 7..7,
+                text: " ".to_owned()
+            }
+        ]
+    );
+}
+
+#[test]
+fn no_edits_when_formatted() {
+    assert!(check("#let x = 4\n").is_empty());
+}
+
+#[test]
+fn edits_over_many_lines() {
+    let edits = check("#let  x=4\n\n\n\n#let y = (a:1,b:2)\n\nα  β");
+    // only whitespace changes, so no edit should replace a whole line
+    assert!(
+        edits
+            .iter()
+            .all(|e| e.range.len() <= 2 && e.text.len() <= 1),
+        "{edits:?}"
+    );
+}
@@ -0,0 +1,47 @@
+use typstfmt::{format_partial, Config};
+
+#[test]
+fn partial_skips_erroneous_nodes() {
+    let input = "#let x=4\n#f(a,b]\n\n#let y=5\n";
+    let formatted = format_partial(input, Config::default()).unwrap();
+    assert_eq!(formatted.output, "#let x = 4\n#f(a,b]\n\n#let y = 5\n");
+    assert_eq!(formatted.skipped, vec![10..15]);
+    assert_eq!(&input[10..15], "f(a,b");
+}
+
+#[test]
+fn partial_without_errors_matches_format() {
+    let input = "#let x=4\n\n#f(a,b)";
+    let formatted = format_partial(input, Config::default()).unwrap();
+    assert_eq!(
+        formatted.output,
+        typstfmt::format(input, Config::default()).unwrap()
+    );
+    assert!(formatted.skipped.is_empty());
+}
+
+#[test]
+fn partial_keeps_erroneous_block() {
+    let input = "#{\nlet a=1\nlet b=\n}\n\nSome   text.\n";
+    let formatted = format_partial(input, Config::default()).unwrap();
+    assert_eq!(formatted.output, "#{\nlet a=1\nlet b=\n}\n\nSome text.\n");
+    assert_eq!(formatted.skipped.len(), 1);
+}
+
+#[test]
+fn partial_skips_whole_top_level_nodes() {
+    let input = "Some   text *with #f(a,b] in   it* and   more\n";
+    let config = Config {
+        markup_wrap: typstfmt::MarkupWrap::Fill,
+        ..Config::default()
+    };
+    let formatted = format_partial(input, config).unwrap();
+    assert_eq!(
+        formatted.output,
+        "Some text *with #f(a,b] in it* and   more\n"
+    );
+    // the unclosed call leaves two erroneous strong nodes, which are each copied through whole
+    assert_eq!(formatted.skipped, vec![12..24, 33..46]);
+    assert_eq!(&input[12..24], "*with #f(a,b");
+    assert_eq!(&input[33..46], "* and   more\n");
+}
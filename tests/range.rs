@@ -0,0 +1,44 @@
+use typstfmt::{format_range, Config, FormatError, FormattedRange};
+
+fn apply(input: &str, formatted: &FormattedRange) -> String {
+    let mut output = input.to_owned();
+    output.replace_range(formatted.range.clone(), &formatted.text);
+    output
+}
+
+#[test]
+fn range_in_markup_expression() {
+    let input = "#let x=4\n#let y=5\n";
+    let formatted = format_range(input, 1..3, Config::default()).unwrap();
+    assert_eq!(formatted.range, 1..8);
+    assert_eq!(formatted.text, "let x = 4");
+    assert_eq!(apply(input, &formatted), "#let x = 4\n#let y=5\n");
+}
+
+#[test]
+fn range_in_code_block_keeps_indent() {
+    let input = "#{\n  let d = (first: \"a much longer value\", second: \"another much longer value\", third: 3)\n  f(a,b)\n}\n";
+    let start = input.find("first").unwrap();
+    let formatted = format_range(input, start..start, Config::default()).unwrap();
+    assert_eq!(
+        apply(input, &formatted),
+        "#{\n  let d = (\n    first: \"a much longer value\",\n    second: \"another much longer value\",\n    third: 3,\n  )\n  f(a,b)\n}\n"
+    );
+}
+
+#[test]
+fn range_covering_whole_document() {
+    let input = "#let x=4\n\n\n#let y=5";
+    let formatted = format_range(input, 0..input.len(), Config::default()).unwrap();
+    assert_eq!(formatted.range, 0..input.len());
+    assert_eq!(formatted.text, "#let x = 4\n\n#let y = 5\n");
+}
+
+#[test]
+fn range_out_of_bounds() {
+    let input = "#let x=4";
+    assert!(matches!(
+        format_range(input, 2..20, Config::default()),
+        Err(FormatError::InvalidRange)
+    ));
+}